@@ -8,7 +8,7 @@ use ic_cdk::{
             provisional::CanisterIdRecord,
         },
     },
-    caller, id, init, query, update,
+    caller, id, init, post_upgrade, query, update,
 };
 
 use ic_scalable_canister::ic_scalable_misc::{
@@ -18,26 +18,13 @@ use ic_scalable_canister::ic_scalable_misc::{
 #[allow(unused_imports)]
 use ic_scalable_canister::{ic_methods, store::Data};
 
-use crate::{
-    store::{ENTRIES, STABLE_DATA},
-    IDENTIFIER_KIND,
-};
+use crate::store::{Store, ENTRIES, STABLE_DATA};
 
 // This call get triggered when a new canister is spun up
 // the data is passed along to the new canister as a byte array
 #[update(guard = "auth")]
 async fn add_entry_by_parent(entry: Vec<u8>) -> Result<(), ApiError> {
-    STABLE_DATA.with(|v| {
-        ENTRIES.with(|entries| {
-            Data::add_entry_by_parent(
-                v,
-                entries,
-                caller(),
-                entry,
-                Some(IDENTIFIER_KIND.to_string()),
-            )
-        })
-    })
+    Store::add_entry_by_parent(caller(), entry).map(|_| ())
 }
 
 // Method to accept cycles when send to this canister
@@ -61,11 +48,23 @@ fn http_request(req: HttpRequest) -> HttpResponse {
 // The parent canister is the canister that spins up this canister
 // the name is a simple identification of what the canister stores
 // the identifier is a incremented number that is used to create a unique name for the canister combined with the name
+// a spare canister is installed as not available and opened when a report is handed over to it
 #[init]
-pub fn init(parent: Principal, name: String, identifier: usize) {
+pub fn init(parent: Principal, name: String, identifier: usize, is_available: Option<bool>) {
     STABLE_DATA.with(|data| {
         ic_methods::init(data, parent, name, identifier);
-    })
+    });
+    Store::set_available(is_available.unwrap_or(true));
+    Store::set_indexes_complete();
+}
+
+// Rebuilds the secondary indexes after upgrading from a version without (the current version of) the indexes
+// or when the canister was upgraded during a rebuild
+#[post_upgrade]
+pub fn post_upgrade() {
+    if !Store::indexes_complete() {
+        Store::start_index_rebuild();
+    }
 }
 
 // Hacky way to expose the candid interface to the outside world
@@ -2,12 +2,16 @@ use crate::store::STABLE_DATA;
 
 use super::store::Store;
 use candid::Principal;
-use ic_cdk::{caller, query, update};
+use ic_cdk::{api::time, caller, query, update};
 use ic_scalable_canister::ic_scalable_misc::{
     enums::{api_error_type::ApiError, filter_type::FilterType},
     models::paged_response_models::PagedResponse,
 };
-use shared::report_model::{PostReport, ReportFilter, ReportResponse, ReportSort};
+use shared::report_model::{
+    AggregatedResponse, ChildCapacity, CursorPagedResponse, DataChunk, PostReport, PostReportNote,
+    ReportAction, ReportCase, ReportConfig, ReportFilter, ReportFilterExpression, ReportNote,
+    ReportPolicy, ReportQuery, ReportResponse, ReportSort, ReportStats, ReportStatus,
+};
 
 // This method is used to add a report to the canister,
 // The method is async because it optionally creates a new canister
@@ -17,12 +21,45 @@ async fn add_report(
     group_identifier: Principal,
     member_identifier: Principal,
 ) -> Result<ReportResponse, ApiError> {
-    match Store::can_read(caller(), group_identifier, member_identifier).await {
+    Store::check_report_group(&value, group_identifier, "add_report")?;
+
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::Submit,
+    )
+    .await
+    {
         Ok(_caller) => Store::add_report(_caller, value).await,
         Err(err) => Err(err),
     }
 }
 
+// This method is used by the parent canister to add a report on behalf of the reporter
+// the permission is checked for the reporter instead of the caller
+#[update(guard = "is_parent")]
+async fn add_report_by_parent(
+    reporter: Principal,
+    value: PostReport,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<ReportResponse, ApiError> {
+    Store::check_report_group(&value, group_identifier, "add_report_by_parent")?;
+
+    match Store::check_report_permission(
+        reporter,
+        group_identifier,
+        member_identifier,
+        ReportAction::Submit,
+    )
+    .await
+    {
+        Ok(_reporter) => Store::add_report(_reporter, value).await,
+        Err(err) => Err(err),
+    }
+}
+
 // This method is used to get a report from the canister
 #[update(guard = "auth")]
 async fn get_report(
@@ -30,12 +67,108 @@ async fn get_report(
     group_identifier: Principal,
     member_identifier: Principal,
 ) -> Result<ReportResponse, ApiError> {
-    match Store::can_write(caller(), group_identifier, member_identifier).await {
+    // The reporter can view the report with the `view_own` permission
+    let action = match Store::get_report(identifier, group_identifier) {
+        Ok(_report) if _report.reported_by == caller() => ReportAction::ViewOwn,
+        _ => ReportAction::ViewAll,
+    };
+
+    match Store::check_report_permission(caller(), group_identifier, member_identifier, action)
+        .await
+    {
         Ok(_caller) => Store::get_report(identifier, group_identifier),
         Err(err) => Err(err),
     }
 }
 
+// This method is used to move a report through its lifecycle (Open -> InReview -> Resolved / Dismissed)
+#[update(guard = "auth")]
+async fn update_report_status(
+    identifier: Principal,
+    status: ReportStatus,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<ReportResponse, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::Resolve,
+    )
+    .await
+    {
+        Ok(_caller) => Store::update_report_status(identifier, status, group_identifier),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used to add a moderator note to a report
+#[update(guard = "auth")]
+async fn add_report_note(
+    identifier: Principal,
+    value: PostReportNote,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<ReportNote, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::Annotate,
+    )
+    .await
+    {
+        Ok(_caller) => Store::add_report_note(_caller, identifier, value, group_identifier),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used to get the moderator notes of a report, the notes of removed reports can still be read
+#[update(guard = "auth")]
+async fn get_report_notes(
+    identifier: Principal,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<Vec<ReportNote>, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::Annotate,
+    )
+    .await
+    {
+        Ok(_caller) => Store::get_report_notes(identifier, group_identifier),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used by group moderators to delete a report
+#[update(guard = "auth")]
+async fn delete_report(
+    identifier: Principal,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<(), ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::Delete,
+    )
+    .await
+    {
+        Ok(_caller) => Store::delete_report(_caller, identifier, group_identifier),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used by the principal that filed the report to retract it
+#[update(guard = "auth")]
+fn retract_report(identifier: Principal) -> Result<(), ApiError> {
+    Store::retract_report(caller(), identifier)
+}
+
 // This method is used to get reports filtered and sorted with pagination
 #[update(guard = "auth")]
 async fn get_reports(
@@ -47,7 +180,14 @@ async fn get_reports(
     group_identifier: Principal,
     member_identifier: Principal,
 ) -> Result<PagedResponse<ReportResponse>, ApiError> {
-    match Store::can_write(caller(), group_identifier, member_identifier).await {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::ViewAll,
+    )
+    .await
+    {
         Ok(_caller) => Ok(Store::get_reports(
             limit,
             page,
@@ -60,22 +200,257 @@ async fn get_reports(
     }
 }
 
-// COMPOSITE_QUERY PREPARATION
-// This methods is used by the parent canister to get filtered reports from the (this) child canister
-// Data serialized and send as byte array chunks ` (bytes, (start_chunk, end_chunk)) `
-// The parent canister can then deserialize the data and pass it to the frontend
-#[query]
-fn get_chunked_data(
+// This method is used to get reports that match a nested filter expression sorted with pagination
+#[update(guard = "auth")]
+async fn get_reports_by_filter(
+    limit: usize,
+    page: usize,
+    sort: Vec<ReportSort>,
+    expression: ReportFilterExpression,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<PagedResponse<ReportResponse>, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::ViewAll,
+    )
+    .await
+    {
+        Ok(_caller) => Ok(Store::get_reports_by_filter(
+            limit,
+            page,
+            sort,
+            expression,
+            group_identifier,
+        )),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used to get reports filtered and sorted, the next page is requested with the returned cursor
+#[update(guard = "auth")]
+async fn get_reports_by_cursor(
+    limit: usize,
+    cursor: Option<String>,
+    sort: ReportSort,
     filters: Vec<ReportFilter>,
     filter_type: FilterType,
-    chunk: usize,
-    max_bytes_per_chunk: usize,
-) -> (Vec<u8>, (usize, usize)) {
-    if caller() != STABLE_DATA.with(|data| data.borrow().get().parent) {
-        return (vec![], (0, 0));
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<CursorPagedResponse<ReportResponse>, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::ViewAll,
+    )
+    .await
+    {
+        Ok(_caller) => Store::get_reports_by_cursor(
+            limit,
+            cursor,
+            sort,
+            filters,
+            filter_type,
+            group_identifier,
+        ),
+        Err(err) => Err(err),
     }
+}
 
-    Store::get_chunked_data(filters, filter_type, chunk, max_bytes_per_chunk)
+// This method is used to search the report messages of a group for keywords
+#[update(guard = "auth")]
+async fn search_reports(
+    query: String,
+    limit: usize,
+    page: usize,
+    sort: ReportSort,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<PagedResponse<ReportResponse>, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::ViewAll,
+    )
+    .await
+    {
+        Ok(_caller) => Ok(Store::search_reports(
+            query,
+            limit,
+            page,
+            sort,
+            group_identifier,
+        )),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used to get the reports of a group merged per subject with the list of reporters
+#[update(guard = "auth")]
+async fn get_report_cases(
+    limit: usize,
+    page: usize,
+    filters: Vec<ReportFilter>,
+    filter_type: FilterType,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<PagedResponse<ReportCase>, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::ViewAll,
+    )
+    .await
+    {
+        Ok(_caller) => Ok(Store::get_report_cases(
+            limit,
+            page,
+            filters,
+            filter_type,
+            group_identifier,
+        )),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used by the parent canister to merge the cases of all child canisters
+#[query(guard = "is_parent")]
+fn get_report_cases_by_parent(
+    filters: Vec<ReportFilter>,
+    filter_type: FilterType,
+    group_identifier: Principal,
+) -> Vec<ReportCase> {
+    Store::get_all_report_cases(filters, filter_type, group_identifier)
+}
+
+// This method is used by the parent canister to check if a report is a duplicate of a report on this canister
+#[query(guard = "is_parent")]
+fn has_duplicate_report(
+    reporter: Principal,
+    subject: Principal,
+    group_identifier: Principal,
+) -> bool {
+    Store::has_duplicate_report(reporter, subject, group_identifier, time())
+}
+
+// This method is used to get the report counts of a group
+#[update(guard = "auth")]
+async fn get_report_stats(
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<ReportStats, ApiError> {
+    match Store::check_report_permission(
+        caller(),
+        group_identifier,
+        member_identifier,
+        ReportAction::ViewAll,
+    )
+    .await
+    {
+        Ok(_caller) => Ok(Store::get_report_stats(group_identifier)),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used by the parent canister to combine the report counts of all child canisters
+#[query(guard = "is_parent")]
+fn get_report_stats_by_parent(group_identifier: Principal) -> ReportStats {
+    Store::get_report_stats(group_identifier)
+}
+
+// This method is used to get the reports filed by the caller filtered and sorted with pagination
+#[query(guard = "auth")]
+fn get_my_reports(
+    limit: usize,
+    page: usize,
+    sort: ReportSort,
+    filters: Vec<ReportFilter>,
+    filter_type: FilterType,
+) -> PagedResponse<ReportResponse> {
+    Store::get_my_reports(caller(), limit, page, sort, filters, filter_type)
+}
+
+// This method is used to get the report policy of a group
+#[update(guard = "auth")]
+async fn get_report_policy(
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<ReportPolicy, ApiError> {
+    match Store::can_read(caller(), group_identifier, member_identifier).await {
+        Ok(_caller) => Ok(Store::get_report_policy(group_identifier)),
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used by group owners to change which permission is needed for each report action
+#[update(guard = "auth")]
+async fn set_report_policy(
+    policy: ReportPolicy,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<AggregatedResponse<ReportPolicy>, ApiError> {
+    match Store::is_group_owner(caller(), group_identifier, member_identifier).await {
+        Ok(_caller) => Store::set_report_policy(group_identifier, policy).await,
+        Err(err) => Err(err),
+    }
+}
+
+// This method is used by the parent canister to push the report policies that are set on other child canisters
+#[update(guard = "is_parent")]
+fn set_report_policies(policies: Vec<(Principal, ReportPolicy)>) -> Result<(), ApiError> {
+    Store::set_report_policies(policies);
+    Ok(())
+}
+
+// This method is used by the parent canister to store a report that is handed over by a full sibling canister
+#[update(guard = "is_parent")]
+fn add_handed_over_report(entry: Vec<u8>) -> Result<ReportResponse, ApiError> {
+    // The parent only hands over to the canister that replaces the full canister, so a spare canister is opened
+    Store::set_available(true);
+    Store::add_entry_by_parent(caller(), entry)
+}
+
+// This method is used by the parent canister to check the fill level of this canister
+#[query(guard = "is_parent")]
+fn get_capacity() -> ChildCapacity {
+    Store::get_capacity()
+}
+
+// This method is used by the parent canister to record which groups have reports on this canister
+#[query(guard = "is_parent")]
+fn get_group_identifiers() -> Vec<Principal> {
+    Store::get_group_identifiers()
+}
+
+// This method is used to get the configuration set by the parent canister
+#[query]
+fn get_report_config() -> ReportConfig {
+    Store::get_report_config()
+}
+
+// This method is used by the parent canister to update the configuration
+#[update(guard = "is_parent")]
+fn set_report_config(config: ReportConfig) -> Result<(), ApiError> {
+    Store::set_report_config(config)
+}
+
+// COMPOSITE_QUERY PREPARATION
+// This methods is used by the parent canister to get the first reports of a query from the (this) child canister
+// the serialized `CursorPagedResponse` is send in chunks, the first call returns the snapshot id
+// that needs to be passed to get the next chunks of the same data
+#[query(guard = "is_parent")]
+fn get_chunked_data_by_query(
+    query: ReportQuery,
+    snapshot_id: Option<u64>,
+    chunk: usize,
+    max_bytes_per_chunk: usize,
+) -> Result<DataChunk, ApiError> {
+    Store::get_chunked_data_by_query(query, snapshot_id, chunk, max_bytes_per_chunk)
 }
 
 pub fn auth() -> Result<(), String> {
@@ -84,3 +459,10 @@ pub fn auth() -> Result<(), String> {
         false => Ok(()),
     }
 }
+
+pub fn is_parent() -> Result<(), String> {
+    match caller() == STABLE_DATA.with(|data| data.borrow().get().parent) {
+        true => Ok(()),
+        false => Err("Unauthorized".to_string()),
+    }
+}
@@ -1,18 +1,21 @@
 use candid::Principal;
-use ic_cdk::api::time;
+use ic_cdk::{
+    api::{call, time},
+    id,
+};
+use ic_cdk_timers::set_timer;
 use ic_scalable_canister::ic_scalable_misc::{
     enums::{
         api_error_type::{ApiError, ApiErrorType},
         filter_type::FilterType,
-        sort_type::SortDirection,
     },
     helpers::{
-        error_helper::api_error,
-        paging_helper::get_paged_data,
-        role_helper::{default_roles, get_group_roles, get_member_roles, has_permission},
+        error_helper::api_error, paging_helper::get_paged_data, role_helper::has_permission,
         serialize_helper::serialize,
     },
     models::{
+        date_models::DateRange,
+        group_role::GroupRole,
         identifier_model::Identifier,
         paged_response_models::PagedResponse,
         permissions_models::{PermissionActionType, PermissionType},
@@ -20,11 +23,31 @@ use ic_scalable_canister::ic_scalable_misc::{
 };
 use ic_scalable_canister::store::Data;
 
-use std::{cell::RefCell, collections::HashMap};
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, VecDeque},
+    ops::Bound,
+    thread::LocalKey,
+    time::Duration,
+};
 
-use shared::report_model::{PostReport, Report, ReportFilter, ReportResponse, ReportSort};
+use shared::{
+    chunk_helper::{get_chunk_ranges, get_data_chunk, MAX_BYTES_PER_CHUNK},
+    permission_helper::{check_report_permission, get_roles},
+    query_engine::{
+        filter_reports, get_cursor_paged_data, get_top_reports, order_reports, query_reports,
+        tokenize_message,
+    },
+    report_model::{
+        AggregatedResponse, ChildCapacity, CursorPagedResponse, DataChunk, FailedChild, PostReport,
+        PostReportNote, RateLimit, RemovalKind, Report, ReportAction, ReportCase, ReportConfig,
+        ReportCursor, ReportFilter, ReportFilterExpression, ReportNote, ReportNotes, ReportPolicy,
+        ReportQuery, ReportRemoval, ReportResponse, ReportSort, ReportStats, ReportStatus,
+    },
+    validate_helper::validate_post_report,
+};
 
-use crate::{validate::validate_post_report, IDENTIFIER_KIND};
+use crate::{validate::validate_post_report_note, IDENTIFIER_KIND};
 
 use ic_stable_structures::{
     memory_manager::{MemoryId, MemoryManager, VirtualMemory},
@@ -32,9 +55,26 @@ use ic_stable_structures::{
 };
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
+type ReportIndex = LocalKey<RefCell<StableBTreeMap<String, (), Memory>>>;
 
 pub static DATA_MEMORY_ID: MemoryId = MemoryId::new(0);
 pub static ENTRIES_MEMORY_ID: MemoryId = MemoryId::new(1);
+pub static NOTES_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub static POLICIES_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub static CONFIG_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub static GROUP_INDEX_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub static SUBJECT_INDEX_MEMORY_ID: MemoryId = MemoryId::new(6);
+pub static REPORTER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(7);
+pub static CREATED_ON_INDEX_MEMORY_ID: MemoryId = MemoryId::new(8);
+pub static MESSAGE_INDEX_MEMORY_ID: MemoryId = MemoryId::new(9);
+pub static INDEX_VERSION_MEMORY_ID: MemoryId = MemoryId::new(10);
+pub static DATA_VERSION_MEMORY_ID: MemoryId = MemoryId::new(11);
+
+// Version of the secondary indexes, increase it when an index is added or changed so the indexes are rebuilt
+const INDEX_VERSION: u64 = 1;
+
+// The number of reports that are added to the indexes in a single message when the indexes are rebuilt
+const INDEX_REBUILD_BATCH_SIZE: usize = 1_000;
 thread_local! {
     pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
     RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
@@ -52,10 +92,133 @@ thread_local! {
             MEMORY_MANAGER.with(|m| m.borrow().get(ENTRIES_MEMORY_ID)),
         )
     );
+
+    // Moderator notes keyed by the report identifier
+    pub static NOTES: RefCell<StableBTreeMap<String, ReportNotes, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(NOTES_MEMORY_ID)),
+        )
+    );
+
+    // Report policies keyed by the group identifier
+    pub static POLICIES: RefCell<StableBTreeMap<String, ReportPolicy, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(POLICIES_MEMORY_ID)),
+        )
+    );
+
+    // Submission timestamps used for rate limiting, kept on the heap so they reset on upgrade
+    pub static RATE_LIMITS: RefCell<HashMap<RateLimitKey, VecDeque<u64>>> = RefCell::new(HashMap::new());
+
+    // Configuration set by the parent canister
+    pub static CONFIG: RefCell<StableCell<ReportConfig, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONFIG_MEMORY_ID)),
+            ReportConfig::default(),
+        ).expect("failed")
+    );
+
+    // Secondary indexes of the active reports, keyed by `value|identifier`
+    pub static GROUP_INDEX: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_INDEX_MEMORY_ID)),
+        )
+    );
+
+    pub static SUBJECT_INDEX: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SUBJECT_INDEX_MEMORY_ID)),
+        )
+    );
+
+    pub static REPORTER_INDEX: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REPORTER_INDEX_MEMORY_ID)),
+        )
+    );
+
+    // The created on value is zero padded so the keys are ordered by date
+    pub static CREATED_ON_INDEX: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CREATED_ON_INDEX_MEMORY_ID)),
+        )
+    );
+
+    // Version of the indexes once they are complete, 0 while the indexes are being rebuilt
+    pub static INDEX_VERSION_CELL: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(INDEX_VERSION_MEMORY_ID)),
+            0,
+        ).expect("failed")
+    );
+
+    // Increased every time a report is added or changed, used as the snapshot id of the chunks
+    pub static DATA_VERSION: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DATA_VERSION_MEMORY_ID)),
+            0,
+        ).expect("failed")
+    );
+
+    // The identifier of the last report that is added to the indexes during a rebuild
+    static INDEX_REBUILD_CURSOR: RefCell<Option<String>> = const { RefCell::new(None) };
+
+    // Inverted index of the lowercase tokens of the report messages
+    pub static MESSAGE_INDEX: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MESSAGE_INDEX_MEMORY_ID)),
+        )
+    );
 }
 
 pub struct Store;
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Caller(Principal),
+    Group(Principal),
+}
+
+// The secondary indexes of the reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IndexKind {
+    Group,
+    Subject,
+    Reporter,
+    CreatedOn,
+    Message,
+}
+
+impl IndexKind {
+    const ALL: [IndexKind; 5] = [
+        IndexKind::Group,
+        IndexKind::Subject,
+        IndexKind::Reporter,
+        IndexKind::CreatedOn,
+        IndexKind::Message,
+    ];
+
+    fn index(&self) -> &'static ReportIndex {
+        match self {
+            IndexKind::Group => &GROUP_INDEX,
+            IndexKind::Subject => &SUBJECT_INDEX,
+            IndexKind::Reporter => &REPORTER_INDEX,
+            IndexKind::CreatedOn => &CREATED_ON_INDEX,
+            IndexKind::Message => &MESSAGE_INDEX,
+        }
+    }
+
+    fn memory_id(&self) -> MemoryId {
+        match self {
+            IndexKind::Group => GROUP_INDEX_MEMORY_ID,
+            IndexKind::Subject => SUBJECT_INDEX_MEMORY_ID,
+            IndexKind::Reporter => REPORTER_INDEX_MEMORY_ID,
+            IndexKind::CreatedOn => CREATED_ON_INDEX_MEMORY_ID,
+            IndexKind::Message => MESSAGE_INDEX_MEMORY_ID,
+        }
+    }
+}
+
 impl Store {
     // Method to add a report to the canister
     pub async fn add_report(
@@ -65,14 +228,22 @@ impl Store {
         match validate_post_report(post_report.clone()) {
             Err(err) => Err(err),
             Ok(_) => {
+                let now = time();
+                let group_identifier = post_report.group_identifier;
+                Self::check_duplicate_report(caller, &post_report)?;
+                Self::check_rate_limits(caller, group_identifier, now)?;
+
                 let new_report = Report {
                     reported_by: caller,
                     subject: post_report.subject,
+                    reason: Some(post_report.reason.unwrap_or_default()),
                     message: post_report.message,
-                    created_on: time(),
-                    group_identifier: post_report.group_identifier,
+                    created_on: now,
+                    group_identifier,
+                    status: Some(ReportStatus::Open),
+                    removed: None,
                 };
-                match STABLE_DATA.with(|data| {
+                let result = match STABLE_DATA.with(|data| {
                     ENTRIES.with(|entries| {
                         Data::add_entry(
                             data,
@@ -83,397 +254,1618 @@ impl Store {
                     })
                 }) {
                     Err(err) => match err {
-                        ApiError::CanisterAtCapacity(message) => {
-                            let _data = STABLE_DATA.with(|v| v.borrow().get().clone());
-                            match Data::spawn_sibling(&_data, new_report).await {
-                                Ok(_) => Err(ApiError::CanisterAtCapacity(message)),
-                                Err(err) => Err(err),
-                            }
-                        }
+                        // This canister is full, the report is stored on the sibling canister instead
+                        ApiError::CanisterAtCapacity(_) => Self::hand_over_report(new_report).await,
                         _ => Err(err),
                     },
                     Ok((identifier, report)) => {
+                        Self::index_report(&identifier.to_string(), &report);
+                        Self::increase_data_version();
                         Ok(Self::map_to_report_response(identifier, report))
                     }
+                };
+
+                // A submission only counts against the rate limits once the report is stored
+                if result.is_ok() {
+                    Self::record_rate_limits(caller, group_identifier, now);
                 }
+                result
             }
         }
     }
 
-    // Method to get a single report
-    pub fn get_report(
-        identifier: Principal,
-        group_identifier: Principal,
-    ) -> Result<ReportResponse, ApiError> {
-        STABLE_DATA.with(|data| {
-            ENTRIES.with(|entries| match Data::get_entry(data, entries, identifier) {
-                Err(err) => Err(err),
-                Ok((_identifier, _result)) => {
-                    if _result.group_identifier != group_identifier {
-                        return Err(api_error(
-                            ApiErrorType::Unauthorized,
-                            "REPORT_NOT_FOUND",
-                            "Report does not belong to the group",
-                            STABLE_DATA
-                                .with(|data| Data::get_name(data.borrow().get()))
-                                .as_str(),
-                            "get_report",
-                            None,
-                        ));
-                    } else {
-                        Ok(Self::map_to_report_response(_identifier, _result))
-                    }
-                }
-            })
-        })
-    }
-
-    // This method is used to get reports filtered and sorted with pagination
-    pub fn get_reports(
-        limit: usize,
-        page: usize,
-        sort: ReportSort,
-        filters: Vec<ReportFilter>,
-        filter_type: FilterType,
+    // Method to check that the report is posted in the group the permission was checked for
+    pub fn check_report_group(
+        post_report: &PostReport,
         group_identifier: Principal,
-    ) -> PagedResponse<ReportResponse> {
-        ENTRIES.with(|entries| {
-            let get_result = Data::get_entries(entries);
-            // Get groups for filtering and sorting
-            let reports: Vec<ReportResponse> = get_result
-                .iter()
-                // Filter reports by group identifier
-                .filter(|r| r.1.group_identifier == group_identifier)
-                .map(|(identifier, report)| {
-                    Self::map_to_report_response(
-                        Principal::from_text(identifier).expect("failed"),
-                        report.clone(),
-                    )
-                })
-                .collect();
+        method_name: &str,
+    ) -> Result<(), ApiError> {
+        if post_report.group_identifier == group_identifier {
+            return Ok(());
+        }
 
-            // Get filtered reports
-            let filtered_reports = Self::get_filtered_reports(reports, filters, filter_type);
-            // Get ordered reports
-            let ordered_reports = Self::get_ordered_reports(filtered_reports, sort);
-            // Paginate reports and return
-            get_paged_data(ordered_reports, limit, page)
-        })
+        let _data = STABLE_DATA.with(|v| v.borrow().get().clone());
+        Err(api_error(
+            ApiErrorType::BadRequest,
+            "GROUP_MISMATCH",
+            "The group of the report does not match the group identifier",
+            Data::get_name(&_data).as_str(),
+            method_name,
+            None,
+        ))
     }
 
-    // Used for composite_query calls from the parent canister
-    //
-    // Method to get filtered groups serialized and chunked
-    pub fn get_chunked_data(
-        filters: Vec<ReportFilter>,
-        filter_type: FilterType,
-        chunk: usize,
-        max_bytes_per_chunk: usize,
-    ) -> (Vec<u8>, (usize, usize)) {
-        // Get reports for filtering
-        let reports = ENTRIES.with(|entries| Data::get_entries(entries));
-        // Map reports to report response
-        let mapped_reports: Vec<ReportResponse> = reports
-            .iter()
-            .map(|(_identifier, _report_data)| {
-                Self::map_to_report_response(
-                    Principal::from_text(_identifier).expect("failed"),
-                    _report_data.clone(),
-                )
-            })
-            .collect();
+    // Method to hand over a report to the parent canister when this canister is full, the parent canister closes
+    // this canister and stores the report on a sibling canister, the stored report is returned
+    async fn hand_over_report(report: Report) -> Result<ReportResponse, ApiError> {
+        let _data = STABLE_DATA.with(|v| v.borrow().get().clone());
+        let last_entry_id = _data.current_entry_id.saturating_sub(1);
 
-        // Get filtered reports
-        let filtered_groups = Self::get_filtered_reports(mapped_reports, filters, filter_type);
-
-        // Serialize filtered reports
-        if let Ok(bytes) = serialize(&filtered_groups) {
-            // Check if the bytes of the serialized reports are greater than the max bytes per chunk specified as an argument
-            if bytes.len() >= max_bytes_per_chunk {
-                // Get the start and end index of the bytes to be returned
-                let start = chunk * max_bytes_per_chunk;
-                let end = (chunk + 1) * (max_bytes_per_chunk);
-
-                // Get the bytes to be returned, if the end index is greater than the length of the bytes, return the remaining bytes
-                let response = if end >= bytes.len() {
-                    bytes[start..].to_vec()
-                } else {
-                    bytes[start..end].to_vec()
-                };
+        let entry = serialize(&report).map_err(|err| {
+            api_error(
+                ApiErrorType::SerializeError,
+                "FAILED_TO_SERIALIZE",
+                err.to_string().as_str(),
+                Data::get_name(&_data).as_str(),
+                "hand_over_report",
+                None,
+            )
+        })?;
 
-                // Determine the max number of chunks that can be returned, a float is used because the number of chunks can be a decimal in this step
-                let mut max_chunks: f64 = 0.00;
-                if max_bytes_per_chunk < bytes.len() {
-                    max_chunks = (bytes.len() / max_bytes_per_chunk) as f64;
-                }
+        let result: Result<(Result<ReportResponse, ApiError>,), _> =
+            call::call(_data.parent, "hand_over_report", (last_entry_id, entry)).await;
 
-                // return the response and start and end chunk index, the end chunk index is calculated by rounding up the max chunks
-                return (response, (chunk, max_chunks.ceil() as usize));
-            }
-            // if the bytes of the serialized groups are less than the max bytes per chunk specified as an argument, return the bytes and start and end chunk index as 0
-            return (bytes, (0, 0));
-        } else {
-            // if the groups cant be serialized return an empty vec and start and end chunk index as 0
-            return (vec![], (0, 0));
+        match result {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "HAND_OVER_FAILED",
+                err.1.as_str(),
+                Data::get_name(&_data).as_str(),
+                "hand_over_report",
+                None,
+            )),
+            Ok((result,)) => result,
         }
     }
 
-    // Method to map report to report response
-    fn map_to_report_response(identifier: Principal, report: Report) -> ReportResponse {
-        ReportResponse {
-            identifier,
-            reported_by: report.reported_by,
-            subject: report.subject,
-            subject_kind: Identifier::kind(&report.subject),
-            message: report.message,
-            created_on: report.created_on,
-            group_identifier: report.group_identifier,
+    // Method to check if the caller already reported the subject in the group within the duplicate window
+    fn check_duplicate_report(caller: Principal, post_report: &PostReport) -> Result<(), ApiError> {
+        if Self::has_duplicate_report(
+            caller,
+            post_report.subject,
+            post_report.group_identifier,
+            time(),
+        ) {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "DUPLICATE_REPORT",
+                "The subject is already reported by the caller",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "add_report",
+                Some(vec![
+                    format!("subject - {}", &post_report.subject),
+                    format!("group_identifier - {}", &post_report.group_identifier),
+                ]),
+            ));
         }
+
+        Ok(())
     }
 
-    // Method to get filtered reports
-    fn get_filtered_reports(
-        mut reports: Vec<ReportResponse>,
-        filters: Vec<ReportFilter>,
-        filter_type: FilterType,
-    ) -> Vec<ReportResponse> {
-        use FilterType::*;
-        match filter_type {
-            // this filter type will return groups that match all the filters
-            And => {
-                for filter in filters {
-                    use ReportFilter::*;
-                    match filter {
-                        Kind(value) => {
-                            reports = reports
-                                .into_iter()
-                                .filter(|report| report.subject_kind.contains(&value))
-                                .collect()
-                        }
-                        CreatedOn(value) => {
-                            reports = reports
-                                .into_iter()
-                                .filter(|report| {
-                                    if value.end_date > 0 {
-                                        return report.created_on >= value.start_date
-                                            && report.created_on <= value.end_date;
-                                    } else {
-                                        return report.created_on >= value.start_date;
-                                    }
-                                })
-                                .collect();
-                        }
-                        ReportedBy(value) => {
-                            reports = reports
-                                .into_iter()
-                                .filter(|report| report.reported_by == value)
-                                .collect()
-                        }
-                    }
-                }
-                reports
-            }
-            // This filter type will return reports that match any of the filters
-            Or => {
-                let mut hashmap_reports: HashMap<Principal, ReportResponse> = HashMap::new();
-                for filter in filters {
-                    use ReportFilter::*;
-                    match filter {
-                        Kind(value) => reports
-                            .iter()
-                            .filter(|report| report.subject_kind.contains(&value))
-                            .for_each(|v| {
-                                hashmap_reports.insert(v.identifier.clone(), v.clone());
-                            }),
-                        CreatedOn(value) => reports
-                            .iter()
-                            .filter(|report| {
-                                if value.end_date > 0 {
-                                    return report.created_on >= value.start_date
-                                        && report.created_on <= value.end_date;
-                                } else {
-                                    return report.created_on >= value.start_date;
-                                }
-                            })
-                            .for_each(|v| {
-                                hashmap_reports.insert(v.identifier.clone(), v.clone());
-                            }),
-                        ReportedBy(value) => reports
-                            .iter()
-                            .filter(|report| report.reported_by == value)
-                            .for_each(|v| {
-                                hashmap_reports.insert(v.identifier.clone(), v.clone());
-                            }),
-                    }
-                }
-                hashmap_reports.into_iter().map(|v| v.1).collect()
-            }
+    // Method to check if the reporter reported the subject in the group within the duplicate window of the config,
+    // used by the parent canister to check the reports on all the child canisters
+    pub fn has_duplicate_report(
+        reporter: Principal,
+        subject: Principal,
+        group_identifier: Principal,
+        now: u64,
+    ) -> bool {
+        let duplicate_window = CONFIG.with(|c| c.borrow().get().duplicate_window);
+        if duplicate_window == 0 {
+            return false;
         }
-    }
 
-    // Method to get sorted reports
-    fn get_ordered_reports(
-        mut reports: Vec<ReportResponse>,
-        sort: ReportSort,
-    ) -> Vec<ReportResponse> {
-        use ReportSort::*;
-        match sort {
-            Id(direction) => match direction {
-                SortDirection::Asc => reports.sort_by(|a, b| {
-                    Identifier::decode(&a.identifier)
-                        .0
-                        .cmp(&Identifier::decode(&b.identifier).0)
-                }),
-                SortDirection::Desc => reports.sort_by(|a, b| {
-                    Identifier::decode(&b.identifier)
-                        .0
-                        .cmp(&Identifier::decode(&a.identifier).0)
-                }),
-            },
-            CreatedOn(direction) => match direction {
-                SortDirection::Asc => reports.sort_by(|a, b| a.created_on.cmp(&b.created_on)),
-                SortDirection::Desc => reports.sort_by(|a, b| b.created_on.cmp(&a.created_on)),
-            },
-            Kind(direction) => match direction {
-                SortDirection::Asc => reports.sort_by(|a, b| a.subject_kind.cmp(&b.subject_kind)),
-                SortDirection::Desc => reports.sort_by(|a, b| b.subject_kind.cmp(&a.subject_kind)),
-            },
-        };
-        reports
+        let window_start = now.saturating_sub(duplicate_window);
+        Self::get_indexed_reports(Some(reporter), IndexKind::Reporter)
+            .iter()
+            .any(|(_, report)| {
+                report.subject == subject
+                    && report.group_identifier == group_identifier
+                    && report.removed.is_none()
+                    && report.created_on >= window_start
+            })
     }
 
-    // This method is used for role / permission based access control
-    pub async fn can_write(
+    // Method to check the caller and group rate limits, the submission is counted with `record_rate_limits`
+    fn check_rate_limits(
         caller: Principal,
         group_identifier: Principal,
-        member_identifier: Principal,
-    ) -> Result<Principal, ApiError> {
-        Self::check_permission(
-            caller,
-            group_identifier,
-            member_identifier,
-            PermissionActionType::Write,
-        )
-        .await
+        now: u64,
+    ) -> Result<(), ApiError> {
+        match Self::get_exceeded_rate_limit(caller, group_identifier, now) {
+            None => Ok(()),
+            Some(key) => {
+                let input = match key {
+                    RateLimitKey::Caller(principal) => format!("caller - {}", principal),
+                    RateLimitKey::Group(principal) => format!("group_identifier - {}", principal),
+                };
+                Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "RATE_LIMIT_EXCEEDED",
+                    "Too many reports submitted, try again later",
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    "add_report",
+                    Some(vec![input]),
+                ))
+            }
+        }
     }
 
-    // This method is used for role / permission based access control
-    pub async fn can_read(
+    // Method to get the caller or group limit that is reached, the submissions outside of the window are dropped
+    fn get_exceeded_rate_limit(
         caller: Principal,
         group_identifier: Principal,
-        member_identifier: Principal,
-    ) -> Result<Principal, ApiError> {
-        Self::check_permission(
-            caller,
-            group_identifier,
-            member_identifier,
-            PermissionActionType::Read,
-        )
-        .await
+        now: u64,
+    ) -> Option<RateLimitKey> {
+        let config = CONFIG.with(|c| c.borrow().get().clone());
+
+        RATE_LIMITS.with(|rate_limits| {
+            let mut rate_limits = rate_limits.borrow_mut();
+
+            // Drop the submissions that are outside of the window, keys without submissions are removed
+            // so only the callers and groups that submitted within the window are kept
+            rate_limits.retain(|key, timestamps| {
+                let limit = match key {
+                    RateLimitKey::Caller(_) => &config.caller_rate_limit,
+                    RateLimitKey::Group(_) => &config.group_rate_limit,
+                };
+                let Some(limit) = limit else {
+                    return false;
+                };
+
+                while timestamps
+                    .front()
+                    .is_some_and(|timestamp| timestamp.saturating_add(limit.window) <= now)
+                {
+                    timestamps.pop_front();
+                }
+                !timestamps.is_empty()
+            });
+
+            Self::get_rate_limits(&config, caller, group_identifier)
+                .into_iter()
+                .find(|(key, limit)| {
+                    let submissions = rate_limits
+                        .get(key)
+                        .map_or(0, |timestamps| timestamps.len() as u64);
+                    submissions >= limit.max_reports
+                })
+                .map(|(key, _)| key)
+        })
     }
 
-    // This method is used for role / permission based access control
-    pub async fn can_edit(
+    // Method to count a stored report against the caller and group rate limits
+    fn record_rate_limits(caller: Principal, group_identifier: Principal, now: u64) {
+        let config = CONFIG.with(|c| c.borrow().get().clone());
+        RATE_LIMITS.with(|rate_limits| {
+            let mut rate_limits = rate_limits.borrow_mut();
+            for (key, _) in Self::get_rate_limits(&config, caller, group_identifier) {
+                rate_limits.entry(key).or_default().push_back(now);
+            }
+        });
+    }
+
+    fn get_rate_limits(
+        config: &ReportConfig,
         caller: Principal,
         group_identifier: Principal,
-        member_identifier: Principal,
-    ) -> Result<Principal, ApiError> {
-        Self::check_permission(
-            caller,
-            group_identifier,
-            member_identifier,
-            PermissionActionType::Edit,
-        )
-        .await
+    ) -> Vec<(RateLimitKey, RateLimit)> {
+        vec![
+            config
+                .caller_rate_limit
+                .clone()
+                .map(|limit| (RateLimitKey::Caller(caller), limit)),
+            config
+                .group_rate_limit
+                .clone()
+                .map(|limit| (RateLimitKey::Group(group_identifier), limit)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
     }
 
-    // This method is used for role / permission based access control
-    pub async fn can_delete(
-        caller: Principal,
+    // Method to get a single report
+    pub fn get_report(
+        identifier: Principal,
         group_identifier: Principal,
-        member_identifier: Principal,
-    ) -> Result<Principal, ApiError> {
-        Self::check_permission(
-            caller,
-            group_identifier,
-            member_identifier,
-            PermissionActionType::Delete,
-        )
-        .await
+    ) -> Result<ReportResponse, ApiError> {
+        match Self::get_active_report(identifier, group_identifier, "get_report") {
+            Err(err) => Err(err),
+            Ok((_identifier, _report)) => Ok(Self::map_to_report_response(_identifier, _report)),
+        }
     }
 
-    // This method is used for role / permission based access control
-    async fn check_permission(
-        caller: Principal,
+    // Method to move a report to a new status, only allowed transitions are accepted
+    pub fn update_report_status(
+        identifier: Principal,
+        status: ReportStatus,
         group_identifier: Principal,
-        member_identifier: Principal,
-        permission: PermissionActionType,
-    ) -> Result<Principal, ApiError> {
-        let group_roles = get_group_roles(group_identifier).await;
-        let member_roles = get_member_roles(member_identifier, group_identifier).await;
-
-        match member_roles {
-            Ok((_principal, _roles)) => {
-                if caller != _principal {
-                    return Err(api_error(
-                        ApiErrorType::Unauthorized,
-                        "PRINCIPAL_MISMATCH",
-                        "Principal mismatch",
-                        STABLE_DATA
-                            .with(|data| Data::get_name(data.borrow().get()))
-                            .as_str(),
-                        "check_permission",
-                        None,
-                    ));
-                }
+    ) -> Result<ReportResponse, ApiError> {
+        let (_identifier, mut _report) =
+            Self::get_active_report(identifier, group_identifier, "update_report_status")?;
+
+        let current_status = _report.status.clone().unwrap_or_default();
+        if !current_status.can_transition_to(&status) {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_STATUS_TRANSITION",
+                format!(
+                    "Report status can not change from {:?} to {:?}",
+                    current_status, status
+                )
+                .as_str(),
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "update_report_status",
+                Some(vec![
+                    format!("identifier - {}", &identifier),
+                    format!("status - {:?}", &status),
+                ]),
+            ));
+        }
 
-                match group_roles {
-                    Ok(mut _group_roles) => {
-                        _group_roles.append(&mut default_roles());
-                        let has_permission = has_permission(
-                            &_roles,
-                            &PermissionType::Group(None),
-                            &_group_roles,
-                            &permission,
-                        );
-
-                        if !has_permission {
-                            return Err(api_error(
-                                ApiErrorType::Unauthorized,
-                                "NO_PERMISSION",
-                                "No permission",
-                                STABLE_DATA
-                                    .with(|data| Data::get_name(data.borrow().get()))
-                                    .as_str(),
-                                "check_permission",
-                                None,
-                            ));
-                        }
-
-                        Ok(caller)
+        _report.status = Some(status);
+        STABLE_DATA.with(|data| {
+            ENTRIES.with(
+                |entries| match Data::update_entry(data, entries, _identifier, _report) {
+                    Err(err) => Err(err),
+                    Ok((_identifier, _report)) => {
+                        Self::increase_data_version();
+                        Ok(Self::map_to_report_response(_identifier, _report))
                     }
-                    Err(err) => Err(api_error(
-                        ApiErrorType::Unauthorized,
-                        "NO_PERMISSION",
-                        err.as_str(),
-                        STABLE_DATA
-                            .with(|data| Data::get_name(data.borrow().get()))
-                            .as_str(),
-                        "check_permission",
-                        None,
-                    )),
-                }
-            }
-            Err(err) => Err(api_error(
+                },
+            )
+        })
+    }
+
+    // Method used by group moderators to delete a report
+    pub fn delete_report(
+        caller: Principal,
+        identifier: Principal,
+        group_identifier: Principal,
+    ) -> Result<(), ApiError> {
+        let (_identifier, _report) =
+            Self::get_active_report(identifier, group_identifier, "delete_report")?;
+        Self::remove_report(caller, _identifier, _report, RemovalKind::Deleted)
+    }
+
+    // Method used by the original reporter to withdraw a report
+    pub fn retract_report(caller: Principal, identifier: Principal) -> Result<(), ApiError> {
+        let (_identifier, _report) = STABLE_DATA
+            .with(|data| ENTRIES.with(|entries| Data::get_entry(data, entries, identifier)))?;
+
+        if _report.reported_by != caller || _report.removed.is_some() {
+            return Err(api_error(
                 ApiErrorType::Unauthorized,
-                "NO_PERMISSION",
-                err.as_str(),
+                "REPORT_NOT_FOUND",
+                "Report was not filed by the caller",
                 STABLE_DATA
                     .with(|data| Data::get_name(data.borrow().get()))
                     .as_str(),
-                "check_permission",
-                None,
-            )),
+                "retract_report",
+                Some(vec![format!("identifier - {}", &identifier)]),
+            ));
         }
+
+        Self::remove_report(caller, _identifier, _report, RemovalKind::Retracted)
+    }
+
+    // Replaces the report with a tombstone, the entry itself is kept so the entry ids and backups stay consistent
+    // the moderator notes of the report are kept for audit
+    fn remove_report(
+        caller: Principal,
+        identifier: Principal,
+        mut report: Report,
+        kind: RemovalKind,
+    ) -> Result<(), ApiError> {
+        // The indexes are built from the original report, the message is cleared below
+        let original_report = report.clone();
+        report.message = String::default();
+        report.removed = Some(ReportRemoval {
+            removed_by: caller,
+            removed_on: time(),
+            kind,
+        });
+
+        STABLE_DATA.with(|data| {
+            ENTRIES.with(|entries| Data::update_entry(data, entries, identifier, report))
+        })?;
+        Self::unindex_report(&identifier.to_string(), &original_report);
+        Self::increase_data_version();
+        Ok(())
+    }
+
+    // Method to append a moderator note to a report
+    pub fn add_report_note(
+        caller: Principal,
+        identifier: Principal,
+        post_report_note: PostReportNote,
+        group_identifier: Principal,
+    ) -> Result<ReportNote, ApiError> {
+        validate_post_report_note(post_report_note.clone())?;
+        // Make sure the report exists and belongs to the group
+        Self::get_report(identifier, group_identifier)?;
+
+        let note = ReportNote {
+            author: caller,
+            text: post_report_note.text,
+            resolution: post_report_note.resolution,
+            created_on: time(),
+        };
+
+        NOTES.with(|notes| {
+            let mut report_notes = notes
+                .borrow()
+                .get(&identifier.to_string())
+                .unwrap_or_default();
+            report_notes.0.push(note.clone());
+            notes
+                .borrow_mut()
+                .insert(identifier.to_string(), report_notes);
+        });
+
+        Ok(note)
+    }
+
+    // Method to get the moderator notes of a report in the order they were added,
+    // the notes of removed reports are kept for auditing and can still be read
+    pub fn get_report_notes(
+        identifier: Principal,
+        group_identifier: Principal,
+    ) -> Result<Vec<ReportNote>, ApiError> {
+        match Self::get_group_report(identifier, group_identifier, "get_report_notes") {
+            Err(err) => Err(err),
+            Ok(_) => Ok(NOTES.with(|notes| {
+                notes
+                    .borrow()
+                    .get(&identifier.to_string())
+                    .unwrap_or_default()
+                    .0
+            })),
+        }
+    }
+
+    // This method is used to get reports filtered and sorted with pagination
+    pub fn get_reports(
+        limit: usize,
+        page: usize,
+        sort: ReportSort,
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+        group_identifier: Principal,
+    ) -> PagedResponse<ReportResponse> {
+        let ordered_reports =
+            Self::get_ordered_group_reports(vec![sort], filters, filter_type, group_identifier);
+        // Paginate reports and return
+        get_paged_data(ordered_reports, limit, page)
+    }
+
+    // This method is used to get reports filtered and sorted, the page starts after the cursor
+    pub fn get_reports_by_cursor(
+        limit: usize,
+        cursor: Option<String>,
+        sort: ReportSort,
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+        group_identifier: Principal,
+    ) -> Result<CursorPagedResponse<ReportResponse>, ApiError> {
+        let sorts = vec![sort];
+        let cursor = match cursor {
+            None => None,
+            Some(cursor) => Some(ReportCursor::decode(&cursor, &sorts).map_err(|err| {
+                api_error(
+                    ApiErrorType::BadRequest,
+                    "INVALID_CURSOR",
+                    err.as_str(),
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    "get_reports_by_cursor",
+                    Some(vec![format!("cursor - {}", &cursor)]),
+                )
+            })?),
+        };
+
+        let ordered_reports =
+            Self::get_ordered_group_reports(sorts.clone(), filters, filter_type, group_identifier);
+        Ok(get_cursor_paged_data(
+            ordered_reports,
+            &sorts,
+            cursor,
+            limit,
+        ))
+    }
+
+    fn get_ordered_group_reports(
+        sorts: Vec<ReportSort>,
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+        group_identifier: Principal,
+    ) -> Vec<ReportResponse> {
+        // Get the reports of the group from the group index
+        let reports: Vec<ReportResponse> =
+            Self::get_indexed_reports(Some(group_identifier), IndexKind::Group)
+                .into_iter()
+                // Skip removed reports
+                .filter(|r| r.1.group_identifier == group_identifier && r.1.removed.is_none())
+                .map(|(identifier, report)| {
+                    Self::map_to_report_response(
+                        Principal::from_text(identifier).expect("failed"),
+                        report,
+                    )
+                })
+                .collect();
+
+        // Get filtered and ordered reports
+        let expression = ReportFilterExpression::from_filters(filters, filter_type);
+        order_reports(filter_reports(reports, &expression), &sorts)
+    }
+
+    // This method is used to get the reports of a group that match the filter expression, sorted with pagination
+    pub fn get_reports_by_filter(
+        limit: usize,
+        page: usize,
+        sorts: Vec<ReportSort>,
+        expression: ReportFilterExpression,
+        group_identifier: Principal,
+    ) -> PagedResponse<ReportResponse> {
+        let expression = expression.and(Some(ReportFilter::Group(group_identifier)));
+
+        let filtered_reports = Self::get_reports_by_expression(&expression);
+        let ordered_reports = order_reports(filtered_reports, &sorts);
+        get_paged_data(ordered_reports, limit, page)
+    }
+
+    // This method is used to search the report messages of a group for keywords,
+    // reports are returned when their message contains all the keywords of the query as whole words
+    pub fn search_reports(
+        query: String,
+        limit: usize,
+        page: usize,
+        sort: ReportSort,
+        group_identifier: Principal,
+    ) -> PagedResponse<ReportResponse> {
+        let mut identifiers: Option<BTreeSet<String>> = None;
+        for token in tokenize_message(&query) {
+            let token_identifiers: BTreeSet<String> =
+                Self::get_indexed_identifiers(&token, IndexKind::Message)
+                    .into_iter()
+                    .collect();
+            identifiers = Some(match identifiers {
+                None => token_identifiers,
+                Some(_identifiers) => _identifiers
+                    .intersection(&token_identifiers)
+                    .cloned()
+                    .collect(),
+            });
+        }
+
+        let reports: Vec<ReportResponse> =
+            Self::get_reports_by_identifiers(identifiers.unwrap_or_default().into_iter().collect())
+                .into_iter()
+                .filter(|r| r.1.group_identifier == group_identifier && r.1.removed.is_none())
+                .map(|(identifier, report)| {
+                    Self::map_to_report_response(
+                        Principal::from_text(identifier).expect("failed"),
+                        report,
+                    )
+                })
+                .collect();
+
+        let ordered_reports = order_reports(reports, &[sort]);
+        get_paged_data(ordered_reports, limit, page)
+    }
+
+    // This method is used to get the reports of a group merged per subject, cases with the most reports come first
+    pub fn get_report_cases(
+        limit: usize,
+        page: usize,
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+        group_identifier: Principal,
+    ) -> PagedResponse<ReportCase> {
+        get_paged_data(
+            Self::get_all_report_cases(filters, filter_type, group_identifier),
+            limit,
+            page,
+        )
+    }
+
+    // This method is used to get all the cases of a group, the parent canister merges the cases of all child canisters
+    pub fn get_all_report_cases(
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+        group_identifier: Principal,
+    ) -> Vec<ReportCase> {
+        let reports: Vec<ReportResponse> =
+            Self::get_indexed_reports(Some(group_identifier), IndexKind::Group)
+                .into_iter()
+                .filter(|r| r.1.group_identifier == group_identifier && r.1.removed.is_none())
+                .map(|(identifier, report)| {
+                    Self::map_to_report_response(
+                        Principal::from_text(identifier).expect("failed"),
+                        report,
+                    )
+                })
+                .collect();
+
+        ReportCase::from_reports(filter_reports(
+            reports,
+            &ReportFilterExpression::from_filters(filters, filter_type),
+        ))
+    }
+
+    // This method is used to get the report counts of a group
+    pub fn get_report_stats(group_identifier: Principal) -> ReportStats {
+        let mut stats = ReportStats::default();
+        Self::get_indexed_reports(Some(group_identifier), IndexKind::Group)
+            .into_iter()
+            .filter(|r| r.1.group_identifier == group_identifier && r.1.removed.is_none())
+            .for_each(|(identifier, report)| {
+                stats.add(&Self::map_to_report_response(
+                    Principal::from_text(identifier).expect("failed"),
+                    report,
+                ))
+            });
+        stats
+    }
+
+    // Method to get the configuration set by the parent canister
+    pub fn get_report_config() -> ReportConfig {
+        CONFIG.with(|c| c.borrow().get().clone())
+    }
+
+    // Method to store the configuration set by the parent canister
+    pub fn set_report_config(config: ReportConfig) -> Result<(), ApiError> {
+        CONFIG.with(|c| c.borrow_mut().set(config)).map_or_else(
+            |_| {
+                Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "FAILED_TO_STORE_CONFIG",
+                    "Failed to store the report config",
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    "set_report_config",
+                    None,
+                ))
+            },
+            |_| Ok(()),
+        )
+    }
+
+    // This method is used to get the reports filed by the caller filtered and sorted with pagination
+    pub fn get_my_reports(
+        caller: Principal,
+        limit: usize,
+        page: usize,
+        sort: ReportSort,
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+    ) -> PagedResponse<ReportResponse> {
+        // Get the reports of the caller from the reporter index
+        let reports: Vec<ReportResponse> =
+            Self::get_indexed_reports(Some(caller), IndexKind::Reporter)
+                .into_iter()
+                // Skip removed reports
+                .filter(|r| r.1.reported_by == caller && r.1.removed.is_none())
+                .map(|(identifier, report)| {
+                    Self::map_to_report_response(
+                        Principal::from_text(identifier).expect("failed"),
+                        report,
+                    )
+                })
+                .collect();
+
+        // Filter, order and paginate the reports
+        query_reports(
+            reports,
+            &ReportFilterExpression::from_filters(filters, filter_type),
+            &[sort],
+            limit,
+            page,
+        )
+    }
+
+    // Used for composite_query calls from the parent canister
+    //
+    // Method to get the first reports of the query serialized and chunked, only the reports that are needed
+    // for the page are send to the parent canister, which merges them with the reports of the other children
+    //
+    // The first call (without snapshot id) returns the snapshot id, the data version, with the first chunk.
+    // The chunks are requested with composite query calls, which can not store anything, so the bytes are cut again
+    // for every chunk. The data version only changes when a report is added or changed, so for the same snapshot id
+    // the chunks are cut from the same bytes, if the data changed in between the `SNAPSHOT_EXPIRED` error is returned
+    pub fn get_chunked_data_by_query(
+        query: ReportQuery,
+        snapshot_id: Option<u64>,
+        chunk: usize,
+        max_bytes_per_chunk: usize,
+    ) -> Result<DataChunk, ApiError> {
+        let current_snapshot_id = DATA_VERSION.with(|v| *v.borrow().get());
+        if snapshot_id.is_some_and(|id| id != current_snapshot_id) {
+            return Err(Self::chunk_error(
+                ApiErrorType::BadRequest,
+                "SNAPSHOT_EXPIRED",
+                "The data changed since the first chunk was requested",
+                snapshot_id,
+                chunk,
+            ));
+        }
+
+        let filtered_reports = Self::get_reports_by_expression(&query.expression);
+        let top_reports = get_top_reports(filtered_reports, &query);
+
+        let chunks = Self::get_report_chunks(top_reports, max_bytes_per_chunk).map_err(|err| {
+            Self::chunk_error(
+                ApiErrorType::SerializeError,
+                "FAILED_TO_SERIALIZE",
+                err.as_str(),
+                snapshot_id,
+                chunk,
+            )
+        })?;
+
+        get_data_chunk(chunks, current_snapshot_id, chunk).ok_or_else(|| {
+            Self::chunk_error(
+                ApiErrorType::BadRequest,
+                "CHUNK_OUT_OF_RANGE",
+                "The requested chunk does not exist",
+                snapshot_id,
+                chunk,
+            )
+        })
+    }
+
+    // Method to serialize the reports in chunks that each hold a `CursorPagedResponse` with part of the reports,
+    // the chunks are cut between reports so every chunk can be deserialized on its own
+    // the max bytes per chunk is capped so a chunk always fits in a response
+    fn get_report_chunks(
+        top_reports: CursorPagedResponse<ReportResponse>,
+        max_bytes_per_chunk: usize,
+    ) -> Result<Vec<Vec<u8>>, String> {
+        let max_bytes_per_chunk = max_bytes_per_chunk.min(MAX_BYTES_PER_CHUNK);
+
+        // The bytes of a chunk without reports, the serialized reports are appended to this
+        let overhead = serialize(&CursorPagedResponse::<ReportResponse> {
+            data: vec![],
+            ..top_reports.clone()
+        })
+        .map_err(|err| err.to_string())?
+        .len();
+        let report_sizes = top_reports
+            .data
+            .iter()
+            .map(|report| serialize(report).map(|bytes| bytes.len()))
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|err| err.to_string())?;
+
+        get_chunk_ranges(&report_sizes, overhead, max_bytes_per_chunk)
+            .into_iter()
+            .map(|range| {
+                serialize(&CursorPagedResponse {
+                    data: top_reports.data[range].to_vec(),
+                    ..top_reports.clone()
+                })
+                .map_err(|err| err.to_string())
+            })
+            .collect()
+    }
+
+    // Method to create the error of a chunk request
+    fn chunk_error(
+        error_type: ApiErrorType,
+        tag: &str,
+        message: &str,
+        snapshot_id: Option<u64>,
+        chunk: usize,
+    ) -> ApiError {
+        api_error(
+            error_type,
+            tag,
+            message,
+            STABLE_DATA
+                .with(|data| Data::get_name(data.borrow().get()))
+                .as_str(),
+            "get_chunked_data_by_query",
+            Some(vec![
+                format!("snapshot_id - {:?}", snapshot_id),
+                format!("chunk - {}", chunk),
+            ]),
+        )
+    }
+
+    // Method to add a report to the secondary indexes
+    fn index_report(identifier: &str, report: &Report) {
+        for (kind, value) in Self::get_index_values(report) {
+            kind.index().with(|i| {
+                i.borrow_mut()
+                    .insert(Self::get_index_key(&value, identifier), ())
+            });
+        }
+    }
+
+    // Method to remove a report from the secondary indexes
+    fn unindex_report(identifier: &str, report: &Report) {
+        for (kind, value) in Self::get_index_values(report) {
+            kind.index().with(|i| {
+                i.borrow_mut()
+                    .remove(&Self::get_index_key(&value, identifier))
+            });
+        }
+    }
+
+    fn get_index_values(report: &Report) -> Vec<(IndexKind, String)> {
+        let mut values: Vec<(IndexKind, String)> = vec![
+            (IndexKind::Group, report.group_identifier.to_string()),
+            (IndexKind::Subject, report.subject.to_string()),
+            (IndexKind::Reporter, report.reported_by.to_string()),
+            (IndexKind::CreatedOn, format!("{:020}", report.created_on)),
+        ];
+        tokenize_message(&report.message)
+            .into_iter()
+            .for_each(|token| values.push((IndexKind::Message, token)));
+        values
+    }
+
+    fn get_index_key(value: &str, identifier: &str) -> String {
+        format!("{}|{}", value, identifier)
+    }
+
+    // Method to get the reports of a single principal from a secondary index
+    fn get_indexed_reports(principal: Option<Principal>, kind: IndexKind) -> Vec<(String, Report)> {
+        let Some(principal) = principal else {
+            return vec![];
+        };
+
+        Self::get_reports_by_identifiers(Self::get_indexed_identifiers(
+            &principal.to_string(),
+            kind,
+        ))
+    }
+
+    fn get_indexed_identifiers(value: &str, kind: IndexKind) -> Vec<String> {
+        // The index can be incomplete while it is rebuilt, so the reports are matched directly
+        if !Self::indexes_complete() {
+            return ENTRIES.with(|entries| {
+                entries
+                    .borrow()
+                    .iter()
+                    .filter(|(_, report)| report.removed.is_none())
+                    .filter(|(_, report)| {
+                        Self::get_index_values(report)
+                            .into_iter()
+                            .any(|(_kind, _value)| _kind == kind && _value == value)
+                    })
+                    .map(|(identifier, _)| identifier)
+                    .collect()
+            });
+        }
+
+        let prefix = format!("{}|", value);
+        kind.index().with(|i| {
+            i.borrow()
+                .range(prefix.clone()..)
+                .take_while(|(key, _)| key.starts_with(&prefix))
+                .map(|(key, _)| key[prefix.len()..].to_string())
+                .collect()
+        })
+    }
+
+    // Method to get the reports created within the date range from the created on index
+    fn get_reports_by_date_range(date_range: &DateRange) -> Vec<(String, Report)> {
+        // The index can be incomplete while it is rebuilt, the returned reports are filtered afterwards
+        if !Self::indexes_complete() {
+            return ENTRIES.with(Data::get_entries);
+        }
+
+        let identifiers: Vec<String> = CREATED_ON_INDEX.with(|i| {
+            i.borrow()
+                .range(format!("{:020}|", date_range.start_date)..)
+                .map_while(|(key, _)| {
+                    let (created_on, identifier) = key.split_once('|')?;
+                    let created_on = created_on.parse::<u64>().ok()?;
+                    if date_range.end_date > 0 && created_on > date_range.end_date {
+                        return None;
+                    }
+                    Some(identifier.to_string())
+                })
+                .collect()
+        });
+        Self::get_reports_by_identifiers(identifiers)
+    }
+
+    fn get_reports_by_identifiers(identifiers: Vec<String>) -> Vec<(String, Report)> {
+        ENTRIES.with(|entries| {
+            let entries = entries.borrow();
+            identifiers
+                .into_iter()
+                .filter_map(|identifier| {
+                    entries.get(&identifier).map(|report| (identifier, report))
+                })
+                .collect()
+        })
+    }
+
+    // Method to get the active reports that match the filter expression
+    fn get_reports_by_expression(expression: &ReportFilterExpression) -> Vec<ReportResponse> {
+        let reports: Vec<ReportResponse> = Self::get_candidate_reports(expression)
+            .into_iter()
+            // Removed reports are kept as tombstones and are not returned
+            .filter(|(_, report)| report.removed.is_none())
+            .map(|(identifier, report)| {
+                Self::map_to_report_response(
+                    Principal::from_text(identifier).expect("failed"),
+                    report,
+                )
+            })
+            .collect();
+
+        filter_reports(reports, expression)
+    }
+
+    // Method to get the reports that can match the filter expression, the most selective secondary index is used
+    // and only falls back to all entries if no index applies, the returned reports still need to be filtered
+    fn get_candidate_reports(expression: &ReportFilterExpression) -> Vec<(String, Report)> {
+        let required_filters = expression.get_required_filters();
+
+        if let Some(ReportFilter::Group(principal)) = required_filters
+            .iter()
+            .find(|filter| matches!(filter, ReportFilter::Group(_)))
+        {
+            return Self::get_indexed_reports(Some(*principal), IndexKind::Group);
+        }
+
+        if let Some(ReportFilter::ReportedBy(principal)) = required_filters
+            .iter()
+            .find(|filter| matches!(filter, ReportFilter::ReportedBy(_)))
+        {
+            return Self::get_indexed_reports(Some(*principal), IndexKind::Reporter);
+        }
+
+        if let Some(ReportFilter::Subject(principal)) = required_filters
+            .iter()
+            .find(|filter| matches!(filter, ReportFilter::Subject(_)))
+        {
+            return Self::get_indexed_reports(Some(*principal), IndexKind::Subject);
+        }
+
+        if let Some(ReportFilter::CreatedOn(date_range)) = required_filters
+            .iter()
+            .find(|filter| matches!(filter, ReportFilter::CreatedOn(_)))
+        {
+            return Self::get_reports_by_date_range(date_range);
+        }
+
+        ENTRIES.with(Data::get_entries)
+    }
+
+    // Method to rebuild the secondary indexes from the stored reports, the indexes are cleared and rebuilt
+    // in batches on a timer so a full canister does not hit the instruction limit in a single message,
+    // until the rebuild is complete the queries read all the entries instead of the indexes
+    pub fn start_index_rebuild() {
+        for kind in IndexKind::ALL {
+            kind.index().with(|i| {
+                i.replace(StableBTreeMap::new(
+                    MEMORY_MANAGER.with(|m| m.borrow().get(kind.memory_id())),
+                ))
+            });
+        }
+
+        Self::set_index_version(0);
+        INDEX_REBUILD_CURSOR.with(|c| c.replace(None));
+        Self::schedule_index_rebuild_batch();
+    }
+
+    fn schedule_index_rebuild_batch() {
+        set_timer(Duration::ZERO, || {
+            if !Self::rebuild_index_batch() {
+                Self::schedule_index_rebuild_batch();
+            }
+        });
+    }
+
+    // Method to add the next batch of reports to the indexes, returns true once all the reports are indexed
+    fn rebuild_index_batch() -> bool {
+        let cursor = INDEX_REBUILD_CURSOR.with(|c| c.borrow().clone());
+        let batch: Vec<(String, Report)> = ENTRIES.with(|entries| {
+            let entries = entries.borrow();
+            let start = match cursor {
+                None => Bound::Unbounded,
+                Some(cursor) => Bound::Excluded(cursor),
+            };
+            entries
+                .range((start, Bound::Unbounded))
+                .take(INDEX_REBUILD_BATCH_SIZE)
+                .collect()
+        });
+
+        batch
+            .iter()
+            .filter(|(_, report)| report.removed.is_none())
+            .for_each(|(identifier, report)| Self::index_report(identifier, report));
+
+        if batch.len() < INDEX_REBUILD_BATCH_SIZE {
+            Self::set_index_version(INDEX_VERSION);
+            INDEX_REBUILD_CURSOR.with(|c| c.replace(None));
+            return true;
+        }
+
+        INDEX_REBUILD_CURSOR
+            .with(|c| c.replace(batch.last().map(|(identifier, _)| identifier.clone())));
+        false
+    }
+
+    // Method to expire the snapshots of the chunks, called every time a report is added or changed
+    pub fn increase_data_version() {
+        DATA_VERSION.with(|v| {
+            let version = v.borrow().get().saturating_add(1);
+            let _ = v.borrow_mut().set(version);
+        });
+    }
+
+    // The indexes are complete when the persisted version matches the current version of the indexes,
+    // the version is reset when a rebuild starts and is missing after upgrading from a version without it
+    pub fn indexes_complete() -> bool {
+        INDEX_VERSION_CELL.with(|v| *v.borrow().get() == INDEX_VERSION)
+    }
+
+    // Used when the canister is installed, a new canister has no reports so the indexes are complete
+    pub fn set_indexes_complete() {
+        Self::set_index_version(INDEX_VERSION);
+    }
+
+    fn set_index_version(version: u64) {
+        INDEX_VERSION_CELL.with(|v| {
+            let _ = v.borrow_mut().set(version);
+        });
+    }
+
+    // Method to add an entry that is passed along by the parent canister and add it to the secondary indexes
+    // the stored report is returned so the parent canister can pass it on when a report is handed over
+    // Method to open or close this canister for new reports
+    pub fn set_available(is_available: bool) {
+        STABLE_DATA.with(|data| {
+            let mut _data = data.borrow().get().clone();
+            _data.is_available = is_available;
+            let _ = data.borrow_mut().set(_data);
+        });
+    }
+
+    pub fn add_entry_by_parent(
+        caller: Principal,
+        entry: Vec<u8>,
+    ) -> Result<ReportResponse, ApiError> {
+        let current_entry_id = STABLE_DATA.with(|data| data.borrow().get().current_entry_id);
+
+        STABLE_DATA.with(|v| {
+            ENTRIES.with(|entries| {
+                Data::add_entry_by_parent(
+                    v,
+                    entries,
+                    caller,
+                    entry,
+                    Some(IDENTIFIER_KIND.to_string()),
+                )
+            })
+        })?;
+        Self::increase_data_version();
+
+        let stored_report = Identifier::new(current_entry_id, id(), IDENTIFIER_KIND.to_string())
+            .and_then(|identifier| identifier.encode())
+            .ok()
+            .and_then(|identifier| {
+                ENTRIES
+                    .with(|entries| entries.borrow().get(&identifier.to_string()))
+                    .map(|report| (identifier, report))
+            });
+
+        match stored_report {
+            None => Err(api_error(
+                ApiErrorType::NotFound,
+                "REPORT_NOT_FOUND",
+                "The stored report could not be found",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "add_entry_by_parent",
+                None,
+            )),
+            Some((identifier, report)) => {
+                Self::index_report(&identifier.to_string(), &report);
+                Ok(Self::map_to_report_response(identifier, report))
+            }
+        }
+    }
+
+    // Method to get the fill level of this canister
+    pub fn get_capacity() -> ChildCapacity {
+        ENTRIES.with(|entries| ChildCapacity {
+            entries_count: entries.borrow().len(),
+            max_entries: Data::get_max_entries(entries),
+        })
+    }
+
+    // Method to get the groups that have reports stored on this canister, read from the group index
+    pub fn get_group_identifiers() -> Vec<Principal> {
+        // The index can be incomplete while it is rebuilt, so the groups are read from the reports
+        if !Self::indexes_complete() {
+            let group_identifiers: BTreeSet<Principal> = ENTRIES.with(|entries| {
+                entries
+                    .borrow()
+                    .iter()
+                    .map(|(_, report)| report.group_identifier)
+                    .collect()
+            });
+            return group_identifiers.into_iter().collect();
+        }
+
+        let mut group_identifiers: Vec<Principal> = GROUP_INDEX.with(|index| {
+            index
+                .borrow()
+                .iter()
+                .filter_map(|(key, _)| {
+                    key.split('|')
+                        .next()
+                        .and_then(|group| Principal::from_text(group).ok())
+                })
+                .collect()
+        });
+        group_identifiers.dedup();
+        group_identifiers
+    }
+
+    // Method to get the report policy of a group, groups without a stored policy use the default
+    pub fn get_report_policy(group_identifier: Principal) -> ReportPolicy {
+        POLICIES.with(|policies| {
+            policies
+                .borrow()
+                .get(&group_identifier.to_string())
+                .unwrap_or_default()
+        })
+    }
+
+    // Method to change the report policy of a group, the policy is send to the parent canister
+    // which stores it and pushes it to all the other child canisters so every child uses the same policy,
+    // the policy is only stored here once the parent stored it, the child canisters that missed it are returned
+    pub async fn set_report_policy(
+        group_identifier: Principal,
+        policy: ReportPolicy,
+    ) -> Result<AggregatedResponse<ReportPolicy>, ApiError> {
+        let _data = STABLE_DATA.with(|v| v.borrow().get().clone());
+        let inputs = Some(vec![format!("group_identifier - {}", group_identifier)]);
+
+        let call_result: Result<(Result<Vec<FailedChild>, ApiError>,), _> = call::call(
+            _data.parent,
+            "set_report_policy_by_child",
+            (group_identifier, policy.clone()),
+        )
+        .await;
+
+        let failed_children = match call_result {
+            Err(err) => {
+                return Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "FAILED_TO_REPLICATE_POLICY",
+                    err.1.as_str(),
+                    Data::get_name(&_data).as_str(),
+                    "set_report_policy",
+                    inputs,
+                ))
+            }
+            Ok((result,)) => result?,
+        };
+
+        Self::set_report_policies(vec![(group_identifier, policy.clone())]);
+        Ok(AggregatedResponse::new(policy, failed_children))
+    }
+
+    // Method to store the report policies of groups, used by the parent canister to push the policies
+    // that are set on the other child canisters
+    pub fn set_report_policies(report_policies: Vec<(Principal, ReportPolicy)>) {
+        POLICIES.with(|policies| {
+            let mut policies = policies.borrow_mut();
+            for (group_identifier, policy) in report_policies {
+                policies.insert(group_identifier.to_string(), policy);
+            }
+        });
+    }
+
+    // Method to get a report that belongs to the group and is not removed
+    fn get_active_report(
+        identifier: Principal,
+        group_identifier: Principal,
+        method_name: &str,
+    ) -> Result<(Principal, Report), ApiError> {
+        match Self::get_group_report(identifier, group_identifier, method_name) {
+            Ok((_, _report)) if _report.removed.is_some() => Err(api_error(
+                ApiErrorType::NotFound,
+                "REPORT_NOT_FOUND",
+                "Report has been removed",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                method_name,
+                None,
+            )),
+            result => result,
+        }
+    }
+
+    // Method to get a report of the group, including removed reports
+    fn get_group_report(
+        identifier: Principal,
+        group_identifier: Principal,
+        method_name: &str,
+    ) -> Result<(Principal, Report), ApiError> {
+        STABLE_DATA.with(|data| {
+            ENTRIES.with(|entries| match Data::get_entry(data, entries, identifier) {
+                Err(err) => Err(err),
+                Ok((_identifier, _result)) => {
+                    if _result.group_identifier != group_identifier {
+                        Err(api_error(
+                            ApiErrorType::Unauthorized,
+                            "REPORT_NOT_FOUND",
+                            "Report does not belong to the group",
+                            Data::get_name(data.borrow().get()).as_str(),
+                            method_name,
+                            None,
+                        ))
+                    } else {
+                        Ok((_identifier, _result))
+                    }
+                }
+            })
+        })
+    }
+
+    // Method to map report to report response
+    fn map_to_report_response(identifier: Principal, report: Report) -> ReportResponse {
+        ReportResponse {
+            identifier,
+            reported_by: report.reported_by,
+            subject: report.subject,
+            subject_kind: Identifier::kind(&report.subject),
+            message: report.message,
+            created_on: report.created_on,
+            group_identifier: report.group_identifier,
+            status: report.status.unwrap_or_default(),
+            reason: report.reason.unwrap_or_default(),
+        }
+    }
+
+    // This method is used for role / permission based access control
+    pub async fn can_write(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<Principal, ApiError> {
+        Self::check_permission(
+            caller,
+            group_identifier,
+            member_identifier,
+            PermissionActionType::Write,
+        )
+        .await
+    }
+
+    // This method is used for role / permission based access control
+    pub async fn can_read(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<Principal, ApiError> {
+        Self::check_permission(
+            caller,
+            group_identifier,
+            member_identifier,
+            PermissionActionType::Read,
+        )
+        .await
+    }
+
+    // This method is used for role / permission based access control
+    pub async fn can_edit(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<Principal, ApiError> {
+        Self::check_permission(
+            caller,
+            group_identifier,
+            member_identifier,
+            PermissionActionType::Edit,
+        )
+        .await
+    }
+
+    // This method is used for role / permission based access control
+    pub async fn can_delete(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<Principal, ApiError> {
+        Self::check_permission(
+            caller,
+            group_identifier,
+            member_identifier,
+            PermissionActionType::Delete,
+        )
+        .await
+    }
+
+    // This method is used for report policy based access control
+    pub async fn check_report_permission(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+        action: ReportAction,
+    ) -> Result<Principal, ApiError> {
+        check_report_permission(
+            caller,
+            group_identifier,
+            member_identifier,
+            &Self::get_report_policy(group_identifier),
+            action,
+            STABLE_DATA
+                .with(|data| Data::get_name(data.borrow().get()))
+                .as_str(),
+        )
+        .await
+    }
+
+    // This method is used to make sure only group owners can change the report policy
+    pub async fn is_group_owner(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<Principal, ApiError> {
+        let (member_roles, _) =
+            Self::get_roles(caller, group_identifier, member_identifier).await?;
+
+        if !member_roles.iter().any(|role| role == "owner") {
+            return Err(api_error(
+                ApiErrorType::Unauthorized,
+                "NO_PERMISSION",
+                "Only group owners are allowed to perform this action",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "is_group_owner",
+                None,
+            ));
+        }
+
+        Ok(caller)
+    }
+
+    // This method is used for role / permission based access control
+    async fn check_permission(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+        permission: PermissionActionType,
+    ) -> Result<Principal, ApiError> {
+        let (member_roles, group_roles) =
+            Self::get_roles(caller, group_identifier, member_identifier).await?;
+
+        let has_permission = has_permission(
+            &member_roles,
+            &PermissionType::Group(None),
+            &group_roles,
+            &permission,
+        );
+
+        if !has_permission {
+            return Err(api_error(
+                ApiErrorType::Unauthorized,
+                "NO_PERMISSION",
+                "No permission",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "check_permission",
+                None,
+            ));
+        }
+
+        Ok(caller)
+    }
+
+    // Fetch the roles of the member and the group, the caller needs to be the principal of the member
+    async fn get_roles(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<(Vec<String>, Vec<GroupRole>), ApiError> {
+        get_roles(
+            caller,
+            group_identifier,
+            member_identifier,
+            STABLE_DATA
+                .with(|data| Data::get_name(data.borrow().get()))
+                .as_str(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_report(id: u64, reporter: Principal, subject: Principal, created_on: u64) {
+        let identifier = Identifier::new(id, Principal::from_slice(&[9; 10]), "rpt".to_string())
+            .and_then(|identifier| identifier.encode())
+            .unwrap()
+            .to_string();
+        let report = Report {
+            reported_by: reporter,
+            subject,
+            group_identifier: Principal::from_slice(&[1; 10]),
+            message: String::default(),
+            created_on,
+            status: None,
+            reason: None,
+            removed: None,
+        };
+        ENTRIES.with(|entries| {
+            entries
+                .borrow_mut()
+                .insert(identifier.clone(), report.clone())
+        });
+        Store::index_report(&identifier, &report);
+    }
+
+    #[test]
+    fn permission_checks_use_the_stored_policy() {
+        let group_identifier = Principal::from_slice(&[1]);
+        let other_group_identifier = Principal::from_slice(&[2]);
+
+        let policy = ReportPolicy {
+            view_all: PermissionActionType::Read,
+            ..Default::default()
+        };
+        Store::set_report_policies(vec![(group_identifier, policy)]);
+
+        // The permission check of the endpoints reads the policy of the group
+        assert_eq!(
+            format!(
+                "{:?}",
+                Store::get_report_policy(group_identifier).permission(&ReportAction::ViewAll)
+            ),
+            format!("{:?}", PermissionActionType::Read)
+        );
+        // Groups without a stored policy use the default policy
+        assert_eq!(
+            format!(
+                "{:?}",
+                Store::get_report_policy(other_group_identifier).permission(&ReportAction::ViewAll)
+            ),
+            format!("{:?}", ReportPolicy::default().view_all)
+        );
+    }
+
+    #[test]
+    fn duplicate_reports_are_found_within_the_window() {
+        Store::set_indexes_complete();
+        let group_identifier = Principal::from_slice(&[1; 10]);
+        let reporter = Principal::from_slice(&[2; 10]);
+        let subject = Principal::from_slice(&[3; 10]);
+        let window = ReportConfig::default().duplicate_window;
+        insert_report(0, reporter, subject, window);
+
+        assert!(Store::has_duplicate_report(
+            reporter,
+            subject,
+            group_identifier,
+            window
+        ));
+        assert!(Store::has_duplicate_report(
+            reporter,
+            subject,
+            group_identifier,
+            2 * window
+        ));
+        // After the window, by another reporter, on another subject or in another group it is not a duplicate
+        assert!(!Store::has_duplicate_report(
+            reporter,
+            subject,
+            group_identifier,
+            2 * window + 1
+        ));
+        assert!(!Store::has_duplicate_report(
+            subject,
+            subject,
+            group_identifier,
+            window
+        ));
+        assert!(!Store::has_duplicate_report(
+            reporter,
+            reporter,
+            group_identifier,
+            window
+        ));
+        assert!(!Store::has_duplicate_report(
+            reporter, subject, subject, window
+        ));
+
+        // The check is disabled with a window of 0
+        Store::set_report_config(ReportConfig {
+            duplicate_window: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!Store::has_duplicate_report(
+            reporter,
+            subject,
+            group_identifier,
+            window
+        ));
+    }
+
+    #[test]
+    fn report_cases_merge_the_reports_of_a_subject() {
+        Store::set_indexes_complete();
+        let group_identifier = Principal::from_slice(&[1; 10]);
+        let (first_reporter, second_reporter) = (
+            Principal::from_slice(&[2; 10]),
+            Principal::from_slice(&[3; 10]),
+        );
+        let (subject, other_subject) = (
+            Principal::from_slice(&[4; 10]),
+            Principal::from_slice(&[5; 10]),
+        );
+        insert_report(0, first_reporter, subject, 10);
+        insert_report(1, second_reporter, subject, 30);
+        insert_report(2, first_reporter, other_subject, 20);
+
+        let cases = Store::get_report_cases(10, 1, vec![], FilterType::And, group_identifier);
+        assert_eq!(cases.total, 2);
+        assert_eq!(cases.data[0].subject, subject);
+        assert_eq!(cases.data[0].report_count, 2);
+        assert_eq!(cases.data[0].reporters.len(), 2);
+        assert!(cases.data[0].reporters.contains(&second_reporter));
+        assert_eq!(cases.data[0].first_reported_on, 10);
+        assert_eq!(cases.data[0].last_reported_on, 30);
+        assert_eq!(cases.data[1].subject, other_subject);
+
+        // The cases are filtered before they are merged
+        let cases = Store::get_report_cases(
+            10,
+            1,
+            vec![ReportFilter::ReportedBy(second_reporter)],
+            FilterType::And,
+            group_identifier,
+        );
+        assert_eq!(cases.total, 1);
+        assert_eq!(cases.data[0].report_count, 1);
+    }
+
+    fn set_rate_limits(caller_rate_limit: Option<RateLimit>, group_rate_limit: Option<RateLimit>) {
+        Store::set_report_config(ReportConfig {
+            caller_rate_limit,
+            group_rate_limit,
+            ..Default::default()
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn caller_rate_limit_expires_after_the_window() {
+        let (caller, group_identifier) = (Principal::from_slice(&[1]), Principal::from_slice(&[2]));
+        let limit = RateLimit {
+            max_reports: 2,
+            window: 100,
+        };
+        set_rate_limits(Some(limit), None);
+
+        Store::record_rate_limits(caller, group_identifier, 0);
+        assert_eq!(
+            Store::get_exceeded_rate_limit(caller, group_identifier, 10),
+            None
+        );
+        Store::record_rate_limits(caller, group_identifier, 10);
+        assert_eq!(
+            Store::get_exceeded_rate_limit(caller, group_identifier, 20),
+            Some(RateLimitKey::Caller(caller))
+        );
+        // Other callers are not limited
+        assert_eq!(
+            Store::get_exceeded_rate_limit(group_identifier, group_identifier, 20),
+            None
+        );
+
+        // The first submission leaves the window, the expired entries are removed
+        assert_eq!(
+            Store::get_exceeded_rate_limit(caller, group_identifier, 100),
+            None
+        );
+        assert_eq!(
+            Store::get_exceeded_rate_limit(caller, group_identifier, 110),
+            None
+        );
+        assert!(RATE_LIMITS.with(|r| r.borrow().is_empty()));
+    }
+
+    #[test]
+    fn group_rate_limit_counts_all_callers() {
+        let group_identifier = Principal::from_slice(&[2]);
+        let limit = RateLimit {
+            max_reports: 2,
+            window: 100,
+        };
+        set_rate_limits(None, Some(limit));
+
+        Store::record_rate_limits(Principal::from_slice(&[3]), group_identifier, 0);
+        Store::record_rate_limits(Principal::from_slice(&[4]), group_identifier, 0);
+        assert_eq!(
+            Store::get_exceeded_rate_limit(Principal::from_slice(&[5]), group_identifier, 0),
+            Some(RateLimitKey::Group(group_identifier))
+        );
+        assert_eq!(
+            Store::get_exceeded_rate_limit(
+                Principal::from_slice(&[5]),
+                Principal::from_slice(&[6]),
+                0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn indexes_are_rebuilt_in_batches() {
+        let group_identifier = Principal::from_slice(&[3]);
+        let report_count = INDEX_REBUILD_BATCH_SIZE + 1;
+        ENTRIES.with(|entries| {
+            for id in 0..report_count {
+                entries.borrow_mut().insert(
+                    format!("{:05}", id),
+                    Report {
+                        reported_by: Principal::anonymous(),
+                        subject: Principal::anonymous(),
+                        group_identifier,
+                        message: String::default(),
+                        created_on: id as u64,
+                        status: None,
+                        reason: None,
+                        removed: None,
+                    },
+                );
+            }
+        });
+
+        // Incomplete indexes are not used, the reports are read directly
+        Store::set_index_version(0);
+        assert!(!Store::indexes_complete());
+        assert_eq!(
+            Store::get_indexed_identifiers(&group_identifier.to_string(), IndexKind::Group).len(),
+            report_count
+        );
+
+        assert!(!Store::rebuild_index_batch());
+        assert!(!Store::indexes_complete());
+        assert!(Store::rebuild_index_batch());
+        assert!(Store::indexes_complete());
+        assert_eq!(
+            Store::get_indexed_identifiers(&group_identifier.to_string(), IndexKind::Group).len(),
+            report_count
+        );
     }
 }
@@ -4,12 +4,12 @@ use ic_scalable_canister::ic_scalable_misc::{
     models::validation_models::ValidateField,
 };
 
-use shared::report_model::PostReport;
+use shared::report_model::PostReportNote;
 
-pub fn validate_post_report(post_report: PostReport) -> Result<(), ApiError> {
+pub fn validate_post_report_note(post_report_note: PostReportNote) -> Result<(), ApiError> {
     let validator_fields = vec![ValidateField(
-        ValidationType::StringLength(post_report.message, 0, 500),
-        "message".to_string(),
+        ValidationType::StringLength(post_report_note.text, 1, 500),
+        "text".to_string(),
     )];
 
     Validator(validator_fields).validate()
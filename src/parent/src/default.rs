@@ -1,9 +1,9 @@
 use std::time::Duration;
 
 use ic_cdk::{caller, init, post_upgrade, pre_upgrade, query, storage};
-use ic_cdk_timers::set_timer;
+use ic_cdk_timers::{set_timer, set_timer_interval};
 
-use super::store::{ScalableData, DATA};
+use super::store::{ScalableData, CAPACITY_CHECK_INTERVAL, DATA};
 
 // Stores the data in stable storage before upgrading the canister.
 #[pre_upgrade]
@@ -44,6 +44,8 @@ pub fn post_upgrade() {
             });
         }
     }
+
+    start_capacity_timer();
 }
 
 // Init methods thats get triggered when the canister is installed
@@ -61,6 +63,15 @@ fn init() {
     set_timer(Duration::from_secs(0), || {
         ic_cdk::spawn(ScalableData::initialize_first_child_canister());
     });
+
+    start_capacity_timer();
+}
+
+// Timers are not kept over upgrades, so the capacity timer is started on init and after every upgrade
+fn start_capacity_timer() {
+    set_timer_interval(CAPACITY_CHECK_INTERVAL, || {
+        ic_cdk::spawn(ScalableData::check_capacity());
+    });
 }
 
 // Hacky way to expose the candid interface to the outside world
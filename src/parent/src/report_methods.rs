@@ -1,13 +1,28 @@
-use ic_cdk::query;
+use candid::Principal;
+use ic_cdk::{caller, query, update};
 use ic_scalable_misc::{
-    enums::filter_type::FilterType, models::paged_response_models::PagedResponse,
+    enums::{api_error_type::ApiError, filter_type::FilterType},
+    models::paged_response_models::PagedResponse,
 };
 
-use shared::report_model::{ReportFilter, ReportResponse, ReportSort};
+use shared::report_model::{
+    AggregatedResponse, CursorPagedResponse, PostReport, ReportCase, ReportFilter,
+    ReportFilterExpression, ReportResponse, ReportSort, ReportStats,
+};
 
 use super::store::ScalableData;
 
-// Method used to get all the reports from the child canisters filtered, sorted and paged
+// Method used to submit a report, the report is validated and stored on the available child canister
+#[update(guard = "auth")]
+async fn submit_report(
+    value: PostReport,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<ReportResponse, ApiError> {
+    ScalableData::submit_report(caller(), value, group_identifier, member_identifier).await
+}
+
+// Method used to get the reports of a group from the child canisters filtered, sorted and paged
 // requires composite queries to be released to mainnet
 #[query(composite = true)]
 async fn get_reports(
@@ -16,6 +31,140 @@ async fn get_reports(
     filters: Vec<ReportFilter>,
     filter_type: FilterType,
     sort: ReportSort,
-) -> PagedResponse<ReportResponse> {
-    ScalableData::get_child_canister_data(limit, page, filters, filter_type, sort).await
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<AggregatedResponse<PagedResponse<ReportResponse>>, ApiError> {
+    match ScalableData::can_view_reports(caller(), group_identifier, member_identifier).await {
+        Ok(_caller) => Ok(ScalableData::get_child_canister_data(
+            limit,
+            page,
+            filters,
+            filter_type,
+            sort,
+            Some(group_identifier),
+            None,
+        )
+        .await),
+        Err(err) => Err(err),
+    }
+}
+
+// Method used to get the reports of a group from the child canisters that match a nested filter expression,
+// sorted and paged
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn get_reports_by_filter(
+    limit: usize,
+    page: usize,
+    expression: ReportFilterExpression,
+    sort: Vec<ReportSort>,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<AggregatedResponse<PagedResponse<ReportResponse>>, ApiError> {
+    match ScalableData::can_view_reports(caller(), group_identifier, member_identifier).await {
+        Ok(_caller) => Ok(ScalableData::get_child_canister_data_by_expression(
+            limit,
+            page,
+            expression.and(Some(ReportFilter::Group(group_identifier))),
+            sort,
+        )
+        .await),
+        Err(err) => Err(err),
+    }
+}
+
+// Method used to get the reports of a group from the child canisters filtered and sorted,
+// the next page is requested with the returned cursor
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn get_reports_by_cursor(
+    limit: usize,
+    cursor: Option<String>,
+    filters: Vec<ReportFilter>,
+    filter_type: FilterType,
+    sort: ReportSort,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<AggregatedResponse<CursorPagedResponse<ReportResponse>>, ApiError> {
+    match ScalableData::can_view_reports(caller(), group_identifier, member_identifier).await {
+        Ok(_caller) => {
+            ScalableData::get_child_canister_data_by_cursor(
+                limit,
+                cursor,
+                filters,
+                filter_type,
+                sort,
+                Some(group_identifier),
+                None,
+            )
+            .await
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Method used to get the report counts of a group summed over all the child canisters
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn get_report_stats(
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<AggregatedResponse<ReportStats>, ApiError> {
+    match ScalableData::can_view_reports(caller(), group_identifier, member_identifier).await {
+        Ok(_caller) => Ok(ScalableData::get_child_canister_stats(group_identifier).await),
+        Err(err) => Err(err),
+    }
+}
+
+// Method used to get the reports of a group merged per subject, the cases of all the child canisters are combined
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn get_report_cases(
+    limit: usize,
+    page: usize,
+    filters: Vec<ReportFilter>,
+    filter_type: FilterType,
+    group_identifier: Principal,
+    member_identifier: Principal,
+) -> Result<AggregatedResponse<PagedResponse<ReportCase>>, ApiError> {
+    match ScalableData::can_view_reports(caller(), group_identifier, member_identifier).await {
+        Ok(_caller) => Ok(ScalableData::get_child_canister_cases(
+            limit,
+            page,
+            filters,
+            filter_type,
+            group_identifier,
+        )
+        .await),
+        Err(err) => Err(err),
+    }
+}
+
+// Method used to get the reports filed by the caller from all child canisters filtered, sorted and paged
+// requires composite queries to be released to mainnet
+#[query(composite = true, guard = "auth")]
+async fn get_my_reports(
+    limit: usize,
+    page: usize,
+    filters: Vec<ReportFilter>,
+    filter_type: FilterType,
+    sort: ReportSort,
+) -> AggregatedResponse<PagedResponse<ReportResponse>> {
+    ScalableData::get_child_canister_data(
+        limit,
+        page,
+        filters,
+        filter_type,
+        sort,
+        None,
+        Some(caller()),
+    )
+    .await
+}
+
+pub fn auth() -> Result<(), String> {
+    match caller() == Principal::anonymous() {
+        true => Err("Unauthorized".to_string()),
+        false => Ok(()),
+    }
 }
@@ -4,7 +4,7 @@ use ic_scalable_misc::{
     enums::{api_error_type::ApiError, wasm_version_type::WasmVersion},
     helpers::{
         canister_helper::Canister,
-        metrics_helper::{http_request as _http_request, metrics, PathEntry},
+        metrics_helper::{http_request as _http_request, metrics, MetricsEntry, PathEntry},
     },
     models::{
         canister_models::ScalableCanisterDetails,
@@ -12,6 +12,10 @@ use ic_scalable_misc::{
     },
 };
 
+use shared::report_model::{
+    FailedChild, PlacementStrategy, ReportConfig, ReportPolicy, ReportResponse,
+};
+
 use super::store::{ScalableData, DATA};
 
 // Method to retrieve an available canister to write updated to
@@ -36,6 +40,55 @@ async fn close_child_canister_and_spawn_sibling(
     ScalableData::close_child_canister_and_spawn_sibling(caller(), last_entry_id, entry).await
 }
 
+// Method called by a full child canister to store the report that did not fit on a sibling canister (inter-canister call)
+// can only be called by a child canister
+#[update]
+async fn hand_over_report(last_entry_id: u64, entry: Vec<u8>) -> Result<ReportResponse, ApiError> {
+    ScalableData::hand_over_report(caller(), last_entry_id, entry).await
+}
+
+// Method called by a child canister when a group owner changes the report policy (inter-canister call)
+// the policy is pushed to all the other child canisters, can only be called by a child canister
+#[update]
+async fn set_report_policy_by_child(
+    group_identifier: Principal,
+    policy: ReportPolicy,
+) -> Result<Vec<FailedChild>, ApiError> {
+    ScalableData::set_report_policy_by_child(caller(), group_identifier, policy).await
+}
+
+// Method to retrieve the spare child canister that takes over when the available child canister is full
+#[query]
+fn get_spare_canister() -> Option<Principal> {
+    ScalableData::get_spare_canister()
+}
+
+// Method to get the report config that is pushed to the child canisters
+#[query]
+fn get_report_config() -> ReportConfig {
+    ScalableData::get_report_config()
+}
+
+// Method to change the report config, the config is pushed to all the child canisters
+// the child canisters that did not receive the config are returned, can only be called by a controller of this canister
+#[update(guard = "is_controller")]
+async fn set_report_config(config: ReportConfig) -> Vec<FailedChild> {
+    ScalableData::set_report_config(config).await
+}
+
+// Method to get the strategy that is used to place the reports of a group on the child canisters
+#[query]
+fn get_placement_strategy() -> PlacementStrategy {
+    ScalableData::get_placement_strategy()
+}
+
+// Method to change the placement strategy, with `GroupAffinity` the groups of the stored reports are recorded
+// can only be called by a controller of this canister
+#[update(guard = "is_controller")]
+async fn set_placement_strategy(strategy: PlacementStrategy) -> Result<(), ApiError> {
+    ScalableData::set_placement_strategy(strategy).await
+}
+
 // Method to retrieve the latest wasm version of the child canister that is currently stored
 #[query]
 fn get_latest_wasm_version() -> WasmVersion {
@@ -54,7 +107,14 @@ fn http_request(req: HttpRequest) -> HttpResponse {
                 "content-type".to_string(),
                 "text/plain".to_string(),
             )],
-            body: metrics(vec![]).as_bytes().to_vec(),
+            body: metrics(vec![MetricsEntry {
+                helper_text: "placement_fallbacks The number of times the GroupAffinity placement fell back to Fill"
+                    .to_string(),
+                label: "placement_fallbacks".to_string(),
+                value: ScalableData::get_placement_fallbacks().to_string(),
+            }])
+            .as_bytes()
+            .to_vec(),
         },
     }];
 
@@ -66,3 +126,10 @@ fn http_request(req: HttpRequest) -> HttpResponse {
 fn accept_cycles() -> u64 {
     Canister::accept_cycles()
 }
+
+pub fn is_controller() -> Result<(), String> {
+    match ic_cdk::api::is_controller(&caller()) {
+        true => Ok(()),
+        false => Err("Unauthorized".to_string()),
+    }
+}
@@ -1,6 +1,11 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    time::Duration,
+};
 
 use candid::{CandidType, Deserialize, Principal};
+use futures::{channel::oneshot, stream, StreamExt};
 use ic_cdk::{
     api::{call, time},
     id,
@@ -11,7 +16,6 @@ use ic_scalable_misc::{
         api_error_type::{ApiError, ApiErrorType},
         canister_type::CanisterType,
         filter_type::FilterType,
-        sort_type::SortDirection,
         wasm_version_type::WasmVersion,
     },
     helpers::{
@@ -26,7 +30,36 @@ use ic_scalable_misc::{
     },
 };
 
-use shared::report_model::{ReportFilter, ReportResponse, ReportSort};
+use shared::{
+    chunk_helper::{ChunkVerifier, MAX_BYTES_PER_CHUNK},
+    permission_helper::check_report_permission,
+    query_engine::{get_merged_paged_data, merge_top_reports},
+    report_model::{
+        AggregatedResponse, ChildCapacity, CursorPagedResponse, DataChunk, FailedChild,
+        PlacementStrategy, PostReport, ReportAction, ReportCase, ReportConfig, ReportCursor,
+        ReportFilter, ReportFilterExpression, ReportPolicy, ReportQuery, ReportResponse,
+        ReportSort, ReportStats,
+    },
+    validate_helper::validate_post_report,
+};
+
+// The max number of inter canister calls that are awaited at the same time when fetching data from the child canisters
+const MAX_CONCURRENT_CALLS: usize = 10;
+
+// The max number of times the chunks of a child canister are requested when the data changes in between the chunks
+const MAX_SNAPSHOT_ATTEMPTS: usize = 3;
+
+// The interval in which the fill level of the available child canisters is checked
+pub const CAPACITY_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// The max number of child canisters a submitted report is forwarded to when a child canister is full
+const MAX_SUBMIT_ATTEMPTS: usize = 3;
+
+// A spare child canister is installed once an available child canister is filled above this percentage
+const SPARE_FILL_PERCENTAGE: u64 = 80;
+
+// The max number of times the wait before installing the spare child canister is doubled after a failed install
+const MAX_SPARE_BACKOFF_EXPONENT: u32 = 6;
 
 #[derive(CandidType, Clone, Deserialize)]
 pub struct ScalableMetaData {
@@ -54,6 +87,22 @@ pub struct ScalableData {
     pub updated_at: u64,
     // created_at record
     pub created_at: u64,
+    // The configuration that is pushed to the child canisters
+    pub report_config: Option<ReportConfig>,
+    // Installed child canister that takes over when the available child canister is full
+    pub spare_canister: Option<Principal>,
+    // The strategy used to place the reports of a group on the child canisters, None uses `Fill`
+    pub placement_strategy: Option<PlacementStrategy>,
+    // The child canisters that store reports of a group, the last one is the home of the group
+    // only kept with the `GroupAffinity` strategy
+    pub group_canisters: Option<HashMap<Principal, Vec<Principal>>>,
+    // The report policies of the groups that are pushed to the child canisters
+    pub report_policies: Option<HashMap<Principal, ReportPolicy>>,
+    // The number of times the `GroupAffinity` strategy fell back to `Fill` because the groups
+    // of a closed child canister could not be read
+    pub placement_fallbacks: Option<u64>,
+    // Created canister of which the install as spare child canister failed, the install is retried on this canister
+    pub empty_spare_canister: Option<Principal>,
 }
 
 impl Default for ScalableData {
@@ -65,13 +114,73 @@ impl Default for ScalableData {
             parent: Principal::anonymous(),
             updated_at: time(),
             created_at: time(),
+            report_config: None,
+            spare_canister: None,
+            placement_strategy: None,
+            group_canisters: None,
+            report_policies: None,
+            placement_fallbacks: None,
+            empty_spare_canister: None,
+        }
+    }
+}
+
+// The failed installs of the spare child canister, the capacity timer waits longer after every failed install
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct SpareBackoff {
+    failures: u32,
+    retry_at: u64,
+}
+
+// What the capacity timer does when a spare child canister is needed
+#[derive(Debug, PartialEq)]
+enum SpareInstall {
+    Wait,
+    Spawn,
+    Retry(Principal),
+}
+
+impl SpareBackoff {
+    fn record_failure(self, now: u64) -> Self {
+        let failures = self.failures.saturating_add(1);
+        let wait = (CAPACITY_CHECK_INTERVAL.as_nanos() as u64)
+            .saturating_mul(1 << failures.min(MAX_SPARE_BACKOFF_EXPONENT));
+        SpareBackoff {
+            failures,
+            retry_at: now.saturating_add(wait),
+        }
+    }
+
+    // A canister is only created when the child wasm is present, and the canister of a failed install is reused
+    fn next_install(
+        &self,
+        has_child_wasm: bool,
+        empty_spare_canister: Option<Principal>,
+        now: u64,
+    ) -> SpareInstall {
+        if !has_child_wasm || now < self.retry_at {
+            return SpareInstall::Wait;
+        }
+
+        match empty_spare_canister {
+            Some(canister) => SpareInstall::Retry(canister),
+            None => SpareInstall::Spawn,
         }
     }
 }
 
 thread_local! {
     pub static DATA: RefCell<ScalableData> = RefCell::new(ScalableData::default());
+    // Set while a spare child canister is being installed
+    static SPARE_PENDING: Cell<bool> = const { Cell::new(false) };
+    // The failed installs of the spare child canister, reset on upgrade
+    static SPARE_BACKOFF: Cell<SpareBackoff> = Cell::new(SpareBackoff::default());
+    // Set while a sibling canister is being installed, holds the calls that wait for the same sibling canister
+    static SIBLING_WAITERS: RefCell<Option<Vec<SiblingSender>>> = const { RefCell::new(None) };
 }
+
+type SiblingSender = oneshot::Sender<Result<Principal, ApiError>>;
+
 impl ScalableData {
     // Method to retrieve an available canister to write updates to
     pub fn get_available_canister(caller: Principal) -> Result<ScalableCanisterDetails, String> {
@@ -116,7 +225,7 @@ impl ScalableData {
         }
 
         // spawn empty canister
-        let new_canister = Self::spawn_empty_canister().await;
+        let new_canister = Self::spawn_empty_canister(true).await;
         let _ = match new_canister {
             Err(err) => Err(err),
             Ok(new_canister_principal) => {
@@ -125,6 +234,7 @@ impl ScalableData {
                     Self::get_name(),
                     new_canister_principal,
                     InstallCodeMode::Install,
+                    true,
                 )
                 .await;
                 match installed_canister {
@@ -142,83 +252,563 @@ impl ScalableData {
         entry: Vec<u8>,
     ) -> Result<Principal, ApiError> {
         let inputs = Some(vec![format!("last_entry_id - {:?}", &last_entry_id)]);
+        let sibling_principal = Self::get_sibling_canister(
+            caller,
+            last_entry_id,
+            "close_child_canister_and_spawn_sibling",
+        )
+        .await?;
+
+        // send the entry to the new canister
+        let call_result: Result<(Result<(), ApiError>,), _> =
+            call::call(sibling_principal, "add_entry_by_parent", (entry,)).await;
+
+        match call_result {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "FAILED_TO_STORE_DATA",
+                err.1.as_str(),
+                &Self::get_name(),
+                "close_child_canister_and_spawn_sibling",
+                inputs,
+            )),
+            Ok(_) => Ok(sibling_principal),
+        }
+    }
+
+    // Method called by a full child canister (inter-canister call) to store the report that did not fit
+    // on the sibling canister, the stored report is returned so the report is not rejected
+    pub async fn hand_over_report(
+        caller: Principal,
+        last_entry_id: u64,
+        entry: Vec<u8>,
+    ) -> Result<ReportResponse, ApiError> {
+        let inputs = Some(vec![format!("last_entry_id - {:?}", &last_entry_id)]);
+        let sibling_principal =
+            Self::get_sibling_canister(caller, last_entry_id, "hand_over_report").await?;
+
+        let call_result: Result<(Result<ReportResponse, ApiError>,), _> =
+            call::call(sibling_principal, "add_handed_over_report", (entry,)).await;
+
+        match call_result {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "FAILED_TO_STORE_DATA",
+                err.1.as_str(),
+                &Self::get_name(),
+                "hand_over_report",
+                inputs,
+            )),
+            Ok((result,)) => {
+                if let Ok(report) = &result {
+                    Self::add_group_canister(report.group_identifier, sibling_principal);
+                }
+                result
+            }
+        }
+    }
+
+    // Method to close a full child canister and get the canister that replaces it, the spare child canister
+    // is used when it is installed so the handover is instant, otherwise a new child canister is installed
+    async fn get_sibling_canister(
+        caller: Principal,
+        last_entry_id: u64,
+        method_name: &str,
+    ) -> Result<Principal, ApiError> {
+        let inputs = Some(vec![format!("last_entry_id - {:?}", &last_entry_id)]);
 
         // check if the child wasm is present
-        if DATA.with(|v| v.borrow().child_wasm_data.bytes.len() == 0) {
+        if DATA.with(|v| v.borrow().child_wasm_data.bytes.is_empty()) {
             return Err(api_error(
                 ApiErrorType::BadRequest,
                 "NO_WASM_SPECIFIED",
                 "There is no foundation WASM uploaded",
                 &Self::get_name(),
-                "close_child_canister_and_spawn_sibling",
+                method_name,
                 inputs,
             ));
         }
 
         // check if the caller is known to this canister
-        let caller_canister = DATA.with(|v| v.borrow().canisters.get(&caller).cloned());
-        match caller_canister {
-            None => Err(api_error(
+        let caller_canister = match DATA.with(|v| v.borrow().canisters.get(&caller).cloned()) {
+            None => {
+                return Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "UNKNOWN_CANISTER",
+                    "The caller principal isnt known to this canister",
+                    &Self::get_name(),
+                    method_name,
+                    inputs,
+                ))
+            }
+            Some(_caller_canister) => _caller_canister,
+        };
+
+        // The caller is already closed by an earlier call, use the canister that replaced it
+        if !caller_canister.is_available {
+            if let Ok(available_canister) = Self::get_available_canister(caller) {
+                return Ok(available_canister.principal);
+            }
+        }
+
+        let sibling_principal = match DATA.with(|v| v.borrow_mut().spare_canister.take()) {
+            Some(spare_principal) => spare_principal,
+            None => Self::spawn_sibling_canister(method_name).await?,
+        };
+
+        // close the caller canister and open the sibling canister
+        DATA.with(|v| {
+            let mut data = v.borrow_mut();
+            if let Some(_caller_canister) = data.canisters.get_mut(&caller) {
+                _caller_canister.is_available = false;
+                _caller_canister.entry_range = (0, Some(last_entry_id));
+            }
+            if let Some(sibling_canister) = data.canisters.get_mut(&sibling_principal) {
+                sibling_canister.is_available = true;
+            }
+        });
+
+        // Record the groups of the closed canister, reports can no longer be added to it
+        if Self::get_placement_strategy() == PlacementStrategy::GroupAffinity {
+            match Self::get_child_groups(caller, method_name).await {
+                Ok(group_identifiers) => {
+                    group_identifiers.into_iter().for_each(|group_identifier| {
+                        Self::add_group_canister(group_identifier, caller)
+                    })
+                }
+                Err(err) => {
+                    // Without the groups of the closed canister the recorded groups are incomplete,
+                    // so fall back to querying all the child canisters
+                    ic_cdk::println!("Placement error: {:?}", err);
+                    DATA.with(|v| {
+                        let mut data = v.borrow_mut();
+                        data.placement_strategy = Some(PlacementStrategy::Fill);
+                        data.group_canisters = None;
+                        data.placement_fallbacks =
+                            Some(data.placement_fallbacks.unwrap_or_default() + 1);
+                    });
+                }
+            }
+        }
+
+        Ok(sibling_principal)
+    }
+
+    // Method to spawn and install a new sibling canister, when a sibling canister is already being installed
+    // the call waits for that canister instead of installing another one
+    async fn spawn_sibling_canister(method_name: &str) -> Result<Principal, ApiError> {
+        let receiver = SIBLING_WAITERS.with(|w| {
+            let mut waiters = w.borrow_mut();
+            match waiters.as_mut() {
+                Some(_waiters) => {
+                    let (sender, receiver) = oneshot::channel();
+                    _waiters.push(sender);
+                    Some(receiver)
+                }
+                None => {
+                    *waiters = Some(vec![]);
+                    None
+                }
+            }
+        });
+
+        if let Some(receiver) = receiver {
+            return receiver.await.unwrap_or_else(|_| {
+                Err(api_error(
+                    ApiErrorType::Unexpected,
+                    "SIBLING_INSTALL_CANCELLED",
+                    "The installation of the sibling canister was cancelled",
+                    &Self::get_name(),
+                    method_name,
+                    None,
+                ))
+            });
+        }
+
+        let result = match Self::spawn_empty_canister(true).await {
+            Err(err) => Err(err),
+            Ok(new_canister_principal) => {
+                Self::_install_child_canister(
+                    Self::get_name(),
+                    new_canister_principal,
+                    InstallCodeMode::Install,
+                    true,
+                )
+                .await
+            }
+        };
+
+        let waiters = SIBLING_WAITERS.with(|w| w.borrow_mut().take());
+        for sender in waiters.unwrap_or_default() {
+            let _ = sender.send(result.clone());
+        }
+        result
+    }
+
+    // Method used by the capacity timer, a spare child canister is installed once an available child canister
+    // is filled above the threshold, so a full child canister can hand over to the spare without waiting
+    pub async fn check_capacity() {
+        if DATA.with(|v| v.borrow().spare_canister.is_some()) || SPARE_PENDING.with(|p| p.get()) {
+            return;
+        }
+
+        let spare_install = DATA.with(|v| {
+            let data = v.borrow();
+            SPARE_BACKOFF.with(|b| b.get()).next_install(
+                !data.child_wasm_data.bytes.is_empty(),
+                data.empty_spare_canister,
+                time(),
+            )
+        });
+        if spare_install == SpareInstall::Wait {
+            return;
+        }
+
+        let available_canisters: Vec<Principal> = DATA.with(|data| {
+            data.borrow()
+                .canisters
+                .iter()
+                .filter(|(_, details)| {
+                    details.canister_type == CanisterType::ScalableChild && details.is_available
+                })
+                .map(|(principal, _)| *principal)
+                .collect()
+        });
+
+        for canister in available_canisters {
+            let result: Result<(ChildCapacity,), _> =
+                call::call(canister, "get_capacity", ()).await;
+
+            match result {
+                Ok((capacity,)) => {
+                    if capacity.fill_percentage() >= SPARE_FILL_PERCENTAGE {
+                        if let Err(err) = Self::install_spare_canister(spare_install).await {
+                            ic_cdk::println!("Spare canister error: {:?}", err);
+                        }
+                        return;
+                    }
+                }
+                Err(err) => ic_cdk::println!("Capacity check error: {:?}", err),
+            }
+        }
+    }
+
+    // Method to get the spare child canister that is installed but not yet available
+    pub fn get_spare_canister() -> Option<Principal> {
+        DATA.with(|v| v.borrow().spare_canister)
+    }
+
+    // Method to install a spare child canister, the spare is not available until a child canister hands over to it
+    // when the install fails the created canister is kept and the install is retried on it after a backoff
+    async fn install_spare_canister(spare_install: SpareInstall) -> Result<Principal, ApiError> {
+        SPARE_PENDING.with(|p| p.set(true));
+
+        let canister = match spare_install {
+            SpareInstall::Retry(canister) => Ok(canister),
+            _ => Self::spawn_empty_canister(false).await.inspect(|canister| {
+                DATA.with(|v| v.borrow_mut().empty_spare_canister = Some(*canister));
+            }),
+        };
+
+        let result = match canister {
+            Err(err) => Err(err),
+            Ok(canister) => {
+                Self::_install_child_canister(
+                    Self::get_name(),
+                    canister,
+                    InstallCodeMode::Install,
+                    false,
+                )
+                .await
+            }
+        };
+
+        match result {
+            Ok(spare_principal) => {
+                DATA.with(|v| {
+                    let mut data = v.borrow_mut();
+                    data.spare_canister = Some(spare_principal);
+                    data.empty_spare_canister = None;
+                });
+                SPARE_BACKOFF.with(|b| b.set(SpareBackoff::default()));
+            }
+            Err(_) => SPARE_BACKOFF.with(|b| b.set(b.get().record_failure(time()))),
+        }
+        SPARE_PENDING.with(|p| p.set(false));
+        result
+    }
+
+    // Method used to store a report on the available child canister on behalf of the caller, when the child canister
+    // turns out to be full the report is forwarded to the child canister that replaced it
+    pub async fn submit_report(
+        caller: Principal,
+        value: PostReport,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<ReportResponse, ApiError> {
+        validate_post_report(value.clone())?;
+
+        // The permission is checked for the given group, so the report can only be posted in that group
+        if value.group_identifier != group_identifier {
+            return Err(api_error(
                 ApiErrorType::BadRequest,
-                "UNKNOWN_CANISTER",
-                "The caller principal isnt known to this canister",
+                "GROUP_MISMATCH",
+                "The group of the report does not match the group identifier",
                 &Self::get_name(),
-                "close_child_canister_and_spawn_sibling",
-                inputs,
-            )),
-            Some(mut _caller_canister) => {
-                // spawn empty canister
-                let new_canister = Self::spawn_empty_canister().await;
-                match new_canister {
-                    Err(err) => Err(err),
-                    Ok(new_canister_principal) => {
-                        // Install child canister
-                        let installed_canister = Self::_install_child_canister(
-                            Self::get_name(),
-                            new_canister_principal,
-                            InstallCodeMode::Install,
-                        )
+                "submit_report",
+                None,
+            ));
+        }
+
+        // The reports of the group can be spread over multiple child canisters, so the duplicate check is done
+        // on all of them instead of only on the child canister the report is stored on
+        if Self::has_duplicate_report(caller, &value).await {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "DUPLICATE_REPORT",
+                "The subject is already reported by the caller",
+                &Self::get_name(),
+                "submit_report",
+                Some(vec![
+                    format!("subject - {}", &value.subject),
+                    format!("group_identifier - {}", &value.group_identifier),
+                ]),
+            ));
+        }
+
+        let mut tried_canisters: Vec<Principal> = vec![];
+        for _ in 0..MAX_SUBMIT_ATTEMPTS {
+            let canister_principal =
+                Self::get_submit_canister(value.group_identifier, &tried_canisters);
+
+            let canister_principal = match canister_principal {
+                None => break,
+                Some(_canister_principal) => _canister_principal,
+            };
+            tried_canisters.push(canister_principal);
+
+            let call_result: Result<(Result<ReportResponse, ApiError>,), _> = call::call(
+                canister_principal,
+                "add_report_by_parent",
+                (caller, value.clone(), group_identifier, member_identifier),
+            )
+            .await;
+
+            match call_result {
+                Err(err) => {
+                    return Err(api_error(
+                        ApiErrorType::BadRequest,
+                        "FAILED_TO_STORE_DATA",
+                        err.1.as_str(),
+                        &Self::get_name(),
+                        "submit_report",
+                        Some(vec![format!("canister - {}", canister_principal)]),
+                    ))
+                }
+                // The child canister is full or closed, retry on the canister that is available now
+                Ok((Err(ApiError::CanisterAtCapacity(_)),)) => continue,
+                Ok((Err(ApiError::BadRequest(err)),)) if err.tag == "UNAVAILABLE_CANISTER" => {
+                    continue
+                }
+                Ok((result,)) => {
+                    // The report can be handed over to a sibling canister, so the canister that stores
+                    // the report is read from the identifier
+                    if let Ok(report) = &result {
+                        Self::add_group_canister(
+                            report.group_identifier,
+                            Identifier::principal(&report.identifier),
+                        );
+                    }
+                    return result;
+                }
+            }
+        }
+
+        Err(api_error(
+            ApiErrorType::BadRequest,
+            "NO_AVAILABLE_CANISTER",
+            "No available canister found",
+            &Self::get_name(),
+            "submit_report",
+            None,
+        ))
+    }
+
+    // Method to check if the reporter already reported the subject in the group on any of the child canisters
+    // that hold reports of the group, a child canister that can not be reached checks the report itself when it is stored
+    async fn has_duplicate_report(reporter: Principal, value: &PostReport) -> bool {
+        let (subject, group_identifier) = (value.subject, value.group_identifier);
+        stream::iter(Self::get_child_canisters(Some(group_identifier)))
+            .map(|canister| async move {
+                let result: Result<(bool,), _> = call::call(
+                    canister,
+                    "has_duplicate_report",
+                    (reporter, subject, group_identifier),
+                )
+                .await;
+                result.map(|(is_duplicate,)| is_duplicate).unwrap_or(false)
+            })
+            .buffer_unordered(MAX_CONCURRENT_CALLS)
+            .any(|is_duplicate| async move { is_duplicate })
+            .await
+    }
+
+    // Method to get the available child canister a report is submitted to, with the `GroupAffinity` strategy
+    // the home of the group is used when it is still available
+    fn get_submit_canister(
+        group_identifier: Principal,
+        tried_canisters: &[Principal],
+    ) -> Option<Principal> {
+        DATA.with(|data| {
+            let data = data.borrow();
+            let available_canisters: Vec<Principal> = data
+                .canisters
+                .iter()
+                .filter(|(principal, details)| {
+                    details.canister_type == CanisterType::ScalableChild
+                        && details.is_available
+                        && !tried_canisters.contains(principal)
+                })
+                .map(|(principal, _)| *principal)
+                .collect();
+
+            let home_canister = match data.placement_strategy {
+                Some(PlacementStrategy::GroupAffinity) => data
+                    .group_canisters
+                    .as_ref()
+                    .and_then(|group_canisters| group_canisters.get(&group_identifier))
+                    .and_then(|canisters| {
+                        canisters
+                            .iter()
+                            .rev()
+                            .find(|canister| available_canisters.contains(canister))
+                    })
+                    .copied(),
+                _ => None,
+            };
+
+            home_canister.or(available_canisters.first().copied())
+        })
+    }
+
+    // Method to change the placement strategy, with the `GroupAffinity` strategy the groups of the reports
+    // that are already stored are requested from all the child canisters first
+    pub async fn set_placement_strategy(strategy: PlacementStrategy) -> Result<(), ApiError> {
+        let group_canisters = match strategy {
+            PlacementStrategy::Fill => None,
+            PlacementStrategy::GroupAffinity => {
+                let results: Vec<(Principal, Result<Vec<Principal>, ApiError>)> =
+                    stream::iter(Self::get_child_canisters(None))
+                        .map(|canister| async move {
+                            let result =
+                                Self::get_child_groups(canister, "set_placement_strategy").await;
+                            (canister, result)
+                        })
+                        .buffer_unordered(MAX_CONCURRENT_CALLS)
+                        .collect()
                         .await;
-                        match installed_canister {
-                            Err(err) => Err(err),
-                            Ok(new_installed_canister_principal) => {
-                                // update the caller canister
-                                _caller_canister.is_available = false;
-                                _caller_canister.entry_range = (0, Some(last_entry_id));
-
-                                DATA.with(|v| {
-                                    v.borrow_mut()
-                                        .canisters
-                                        .insert(_caller_canister.principal, _caller_canister)
-                                });
-
-                                // send the entry to the new canister
-                                let call_result: Result<(Result<(), ApiError>,), _> = call::call(
-                                    new_installed_canister_principal,
-                                    "add_entry_by_parent",
-                                    (entry,),
-                                )
-                                .await;
-
-                                match call_result {
-                                    Err(err) => Err(api_error(
-                                        ApiErrorType::BadRequest,
-                                        "FAILED_TO_STORE_DATA",
-                                        err.1.as_str(),
-                                        &Self::get_name(),
-                                        "close_child_canister_and_spawn_sibling",
-                                        inputs,
-                                    )),
-                                    Ok(_) => Ok(new_installed_canister_principal),
-                                }
-                            }
-                        }
+
+                let mut group_canisters: HashMap<Principal, Vec<Principal>> = HashMap::new();
+                for (canister, result) in results {
+                    for group_identifier in result? {
+                        group_canisters
+                            .entry(group_identifier)
+                            .or_default()
+                            .push(canister);
                     }
                 }
+                Some(group_canisters)
+            }
+        };
+
+        DATA.with(|v| {
+            let mut data = v.borrow_mut();
+            data.placement_strategy = Some(strategy);
+            data.group_canisters = group_canisters;
+        });
+        Ok(())
+    }
+
+    pub fn get_placement_strategy() -> PlacementStrategy {
+        DATA.with(|v| v.borrow().placement_strategy.clone().unwrap_or_default())
+    }
+
+    // Method to get the number of times the `GroupAffinity` strategy fell back to `Fill`
+    pub fn get_placement_fallbacks() -> u64 {
+        DATA.with(|v| v.borrow().placement_fallbacks.unwrap_or_default())
+    }
+
+    // Method to record that a child canister stores reports of a group, only with the `GroupAffinity` strategy
+    fn add_group_canister(group_identifier: Principal, canister_principal: Principal) {
+        DATA.with(|v| {
+            let mut data = v.borrow_mut();
+            if data.placement_strategy != Some(PlacementStrategy::GroupAffinity) {
+                return;
             }
+
+            let canisters = data
+                .group_canisters
+                .get_or_insert_with(HashMap::new)
+                .entry(group_identifier)
+                .or_default();
+            // The last canister is the home of the group
+            canisters.retain(|canister| canister != &canister_principal);
+            canisters.push(canister_principal);
+        });
+    }
+
+    // Method to get the groups that have reports stored on a child canister
+    async fn get_child_groups(
+        canister_principal: Principal,
+        method_name: &str,
+    ) -> Result<Vec<Principal>, ApiError> {
+        let call_result: Result<(Vec<Principal>,), _> =
+            call::call(canister_principal, "get_group_identifiers", ()).await;
+
+        match call_result {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "FAILED_TO_GET_GROUPS",
+                err.1.as_str(),
+                &Self::get_name(),
+                method_name,
+                Some(vec![format!("canister - {}", canister_principal)]),
+            )),
+            Ok((group_identifiers,)) => Ok(group_identifiers),
         }
     }
 
+    // Method to get the child canisters that need to be queried, with the `GroupAffinity` strategy
+    // the queries of a single group only go to the child canisters recorded for the group and the available
+    // child canisters, that can have received reports directly, other queries go to all the child canisters
+    fn get_child_canisters(group_identifier: Option<Principal>) -> Vec<Principal> {
+        DATA.with(|data| {
+            let data = data.borrow();
+            let group_canisters = match (&data.placement_strategy, &data.group_canisters) {
+                (Some(PlacementStrategy::GroupAffinity), Some(group_canisters)) => group_identifier
+                    .map(|group_identifier| {
+                        group_canisters
+                            .get(&group_identifier)
+                            .cloned()
+                            .unwrap_or_default()
+                    }),
+                _ => None,
+            };
+
+            data.canisters
+                .iter()
+                .filter(|(_, details)| details.canister_type == CanisterType::ScalableChild)
+                .filter(|(principal, details)| match &group_canisters {
+                    None => true,
+                    Some(group_canisters) => {
+                        details.is_available || group_canisters.contains(principal)
+                    }
+                })
+                .map(|(principal, _)| *principal)
+                .collect()
+        })
+    }
+
     // Method used to upgrade the child canister
     pub async fn upgrade_child_canister(
         canister_principal: Principal,
@@ -286,7 +876,7 @@ impl ScalableData {
     }
 
     // Method used to spawn an empty canister (not installed)
-    async fn spawn_empty_canister() -> Result<Principal, ApiError> {
+    async fn spawn_empty_canister(is_available: bool) -> Result<Principal, ApiError> {
         // Set canister settings
         let canister_settings = CanisterSettings {
             controllers: Some(vec![id()]),
@@ -312,7 +902,7 @@ impl ScalableData {
                     principal: new_canister_principal,
                     wasm_version: WasmVersion::None,
                     canister_type: CanisterType::Empty,
-                    is_available: true,
+                    is_available,
                     entry_range: (0, None),
                 };
 
@@ -332,6 +922,7 @@ impl ScalableData {
         name: String,
         canister_principal: Principal,
         install_code_mode: InstallCodeMode,
+        is_available: bool,
     ) -> Result<Principal, ApiError> {
         let inputs = Some(vec![format!("name - {}", &name.to_string())]);
 
@@ -351,7 +942,7 @@ impl ScalableData {
             .install_code(
                 install_code_mode,
                 data.child_wasm_data.bytes,
-                (id(), name, data.canisters.iter().len()),
+                (id(), name, data.canisters.iter().len(), Some(is_available)),
             )
             .await;
 
@@ -368,7 +959,7 @@ impl ScalableData {
                 let new_child_details = ScalableCanisterDetails {
                     principal: canister_principal,
                     wasm_version: data.child_wasm_data.wasm_version.clone(),
-                    is_available: true,
+                    is_available,
                     canister_type: CanisterType::ScalableChild,
                     entry_range: (0, None),
                 };
@@ -378,11 +969,153 @@ impl ScalableData {
                         .canisters
                         .insert(canister_principal, new_child_details)
                 });
+
+                // Push the report config to the new child canister
+                if let Some(config) = data.report_config {
+                    if let Err(err) = Self::push_report_config(canister_principal, config).await {
+                        ic_cdk::println!("Push report config error: {:?}", err);
+                    }
+                }
+
+                // Push the report policies of the groups to the new child canister
+                if let Some(policies) = data.report_policies {
+                    let policies: Vec<(Principal, ReportPolicy)> = policies.into_iter().collect();
+                    for failed_child in
+                        Self::push_report_policies(vec![canister_principal], policies).await
+                    {
+                        ic_cdk::println!("Push report policies error: {}", failed_child.error);
+                    }
+                }
                 Ok(canister_principal)
             }
         }
     }
 
+    // Method used to store the report config and push it to all the child canisters
+    pub async fn set_report_config(config: ReportConfig) -> Vec<FailedChild> {
+        DATA.with(|v| v.borrow_mut().report_config = Some(config.clone()));
+
+        stream::iter(Self::get_child_canisters(None))
+            .map(|canister| {
+                let config = config.clone();
+                async move {
+                    match Self::push_report_config(canister, config).await {
+                        Ok(()) => None,
+                        Err(err) => Some(FailedChild {
+                            canister,
+                            error: err.to_string(),
+                        }),
+                    }
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_CALLS)
+            .filter_map(|failed_child| async move { failed_child })
+            .collect()
+            .await
+    }
+
+    // Method used to get the report config that is pushed to the child canisters
+    pub fn get_report_config() -> ReportConfig {
+        DATA.with(|v| v.borrow().report_config.clone().unwrap_or_default())
+    }
+
+    // Method used to send the report config to a child canister
+    async fn push_report_config(
+        canister_principal: Principal,
+        config: ReportConfig,
+    ) -> Result<(), ApiError> {
+        let call_result: Result<(Result<(), ApiError>,), _> =
+            call::call(canister_principal, "set_report_config", (config,)).await;
+
+        match call_result {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "FAILED_TO_PUSH_CONFIG",
+                err.1.as_str(),
+                &Self::get_name(),
+                "push_report_config",
+                Some(vec![format!(
+                    "canister_principal - {}",
+                    &canister_principal
+                )]),
+            )),
+            Ok((result,)) => result,
+        }
+    }
+
+    // Method called by a child canister when a group owner changes the report policy of a group (inter-canister call)
+    // the policy is stored and pushed to all the other child canisters, the child canisters that missed it are returned
+    pub async fn set_report_policy_by_child(
+        caller: Principal,
+        group_identifier: Principal,
+        policy: ReportPolicy,
+    ) -> Result<Vec<FailedChild>, ApiError> {
+        if !DATA.with(|v| v.borrow().canisters.contains_key(&caller)) {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "UNKNOWN_CANISTER",
+                "The caller principal isnt known to this canister",
+                &Self::get_name(),
+                "set_report_policy_by_child",
+                None,
+            ));
+        }
+
+        DATA.with(|v| {
+            v.borrow_mut()
+                .report_policies
+                .get_or_insert_with(HashMap::new)
+                .insert(group_identifier, policy.clone())
+        });
+
+        let canisters: Vec<Principal> = Self::get_child_canisters(None)
+            .into_iter()
+            .filter(|canister| canister != &caller)
+            .collect();
+        Ok(Self::push_report_policies(canisters, vec![(group_identifier, policy)]).await)
+    }
+
+    // Method to get the report policy of a group, groups without a stored policy use the default
+    pub fn get_report_policy(group_identifier: Principal) -> ReportPolicy {
+        DATA.with(|v| {
+            v.borrow()
+                .report_policies
+                .as_ref()
+                .and_then(|policies| policies.get(&group_identifier).cloned())
+                .unwrap_or_default()
+        })
+    }
+
+    // Method used to send report policies to the child canisters, the child canisters that could not be updated are returned
+    async fn push_report_policies(
+        canisters: Vec<Principal>,
+        policies: Vec<(Principal, ReportPolicy)>,
+    ) -> Vec<FailedChild> {
+        stream::iter(canisters)
+            .map(|canister| {
+                let policies = policies.clone();
+                async move {
+                    let call_result: Result<(Result<(), ApiError>,), _> =
+                        call::call(canister, "set_report_policies", (policies,)).await;
+                    match call_result {
+                        Ok((Ok(()),)) => None,
+                        Ok((Err(err),)) => Some(FailedChild {
+                            canister,
+                            error: err.to_string(),
+                        }),
+                        Err((code, message)) => Some(FailedChild {
+                            canister,
+                            error: format!("{:?}: {}", code, message),
+                        }),
+                    }
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_CALLS)
+            .filter_map(|failed_child| async move { failed_child })
+            .collect()
+            .await
+    }
+
     // Method used to upgrade all the child canister
     pub async fn upgrade_children() {
         let data = DATA.with(|data| data.borrow().clone());
@@ -427,7 +1160,25 @@ impl ScalableData {
         Ok(details)
     }
 
-    // Method used to get all the reports from the child canisters filtered, sorted and paged
+    // Method used for role / permission based access control on the aggregated report queries
+    // the policy of the group is the same policy that is pushed to the child canisters
+    pub async fn can_view_reports(
+        caller: Principal,
+        group_identifier: Principal,
+        member_identifier: Principal,
+    ) -> Result<Principal, ApiError> {
+        check_report_permission(
+            caller,
+            group_identifier,
+            member_identifier,
+            &Self::get_report_policy(group_identifier),
+            ReportAction::ViewAll,
+            &Self::get_name(),
+        )
+        .await
+    }
+
+    // Method used to get the reports of a group from the child canisters filtered, sorted and paged
     // requires composite queries to be released to mainnet
     pub async fn get_child_canister_data(
         limit: usize,
@@ -435,113 +1186,318 @@ impl ScalableData {
         filters: Vec<ReportFilter>,
         filter_type: FilterType,
         sort: ReportSort,
-    ) -> PagedResponse<ReportResponse> {
-        let canisters: Vec<Principal> = DATA.with(|data| {
-            data.borrow()
-                .canisters
-                .clone()
-                .into_iter()
-                .map(|c| c.1.principal.clone())
-                .collect()
-        });
+        group_identifier: Option<Principal>,
+        reported_by: Option<Principal>,
+    ) -> AggregatedResponse<PagedResponse<ReportResponse>> {
+        let expression = ReportFilterExpression::from_filters(filters, filter_type)
+            .and(group_identifier.map(ReportFilter::Group))
+            .and(reported_by.map(ReportFilter::ReportedBy));
+
+        Self::get_paged_child_data(limit, page, expression, vec![sort]).await
+    }
+
+    // Method to get the reports that match the filter expression from the child canisters, sorted and paged
+    pub async fn get_child_canister_data_by_expression(
+        limit: usize,
+        page: usize,
+        expression: ReportFilterExpression,
+        sorts: Vec<ReportSort>,
+    ) -> AggregatedResponse<PagedResponse<ReportResponse>> {
+        Self::get_paged_child_data(limit, page, expression, sorts).await
+    }
+
+    // Method to get the page of reports from the child canisters that starts after the cursor
+    // the reports of all children are ordered together, so the cursor is valid across canisters
+    pub async fn get_child_canister_data_by_cursor(
+        limit: usize,
+        cursor: Option<String>,
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+        sort: ReportSort,
+        group_identifier: Option<Principal>,
+        reported_by: Option<Principal>,
+    ) -> Result<AggregatedResponse<CursorPagedResponse<ReportResponse>>, ApiError> {
+        let sorts = vec![sort];
+        let cursor = match cursor {
+            None => None,
+            Some(cursor) => Some(ReportCursor::decode(&cursor, &sorts).map_err(|err| {
+                api_error(
+                    ApiErrorType::BadRequest,
+                    "INVALID_CURSOR",
+                    err.as_str(),
+                    &Self::get_name(),
+                    "get_child_canister_data_by_cursor",
+                    Some(vec![format!("cursor - {}", &cursor)]),
+                )
+            })?),
+        };
+
+        let expression = ReportFilterExpression::from_filters(filters, filter_type)
+            .and(group_identifier.map(ReportFilter::Group))
+            .and(reported_by.map(ReportFilter::ReportedBy));
+
+        Ok(Self::get_top_child_data(ReportQuery {
+            expression,
+            sorts,
+            cursor,
+            limit,
+        })
+        .await)
+    }
+
+    // Method to get the report counts of a group summed over all the child canisters
+    // requires composite queries to be released to mainnet
+    pub async fn get_child_canister_stats(
+        group_identifier: Principal,
+    ) -> AggregatedResponse<ReportStats> {
+        let canisters = Self::get_child_canisters(Some(group_identifier));
+
+        // Get the stats from the child canisters concurrently
+        let results: Vec<(Principal, Result<(ReportStats,), _>)> = stream::iter(canisters)
+            .map(|canister| async move {
+                let result =
+                    call::call(canister, "get_report_stats_by_parent", (group_identifier,)).await;
+                (canister, result)
+            })
+            .buffer_unordered(MAX_CONCURRENT_CALLS)
+            .collect()
+            .await;
+
+        let mut stats = ReportStats::default();
+        let mut failed_children: Vec<FailedChild> = vec![];
+        for (canister, result) in results {
+            match result {
+                Ok((_stats,)) => stats.merge(_stats),
+                Err((code, message)) => failed_children.push(FailedChild {
+                    canister,
+                    error: format!("{:?}: {}", code, message),
+                }),
+            }
+        }
+        AggregatedResponse::new(stats, failed_children)
+    }
+
+    // Method to get the cases of a group from the child canisters, the cases about the same subject
+    // on different child canisters are merged before the cases are paged
+    pub async fn get_child_canister_cases(
+        limit: usize,
+        page: usize,
+        filters: Vec<ReportFilter>,
+        filter_type: FilterType,
+        group_identifier: Principal,
+    ) -> AggregatedResponse<PagedResponse<ReportCase>> {
+        let canisters = Self::get_child_canisters(Some(group_identifier));
+
+        // Get the cases from the child canisters concurrently
+        let results: Vec<(Principal, Result<(Vec<ReportCase>,), _>)> = stream::iter(canisters)
+            .map(|canister| {
+                let args = (filters.clone(), filter_type.clone(), group_identifier);
+                async move {
+                    let result = call::call(canister, "get_report_cases_by_parent", args).await;
+                    (canister, result)
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_CALLS)
+            .collect()
+            .await;
 
-        // Create a vector to store the reports
-        let mut reports: Vec<ReportResponse> = vec![];
-        // Get the data from the child canisters
-        for canister in canisters {
-            let mut canister_data =
-                Self::get_filtered_child_data(canister, &filters, &filter_type).await;
-            reports.append(&mut canister_data);
+        let mut cases: Vec<ReportCase> = vec![];
+        let mut failed_children: Vec<FailedChild> = vec![];
+        for (canister, result) in results {
+            match result {
+                Ok((_cases,)) => cases.extend(_cases),
+                Err((code, message)) => failed_children.push(FailedChild {
+                    canister,
+                    error: format!("{:?}: {}", code, message),
+                }),
+            }
         }
-        // Sort the data
-        let ordered_reports = Self::get_ordered_reports(reports, sort);
-        get_paged_data(ordered_reports, limit, page)
+        AggregatedResponse::new(
+            get_paged_data(ReportCase::merge(cases), limit, page),
+            failed_children,
+        )
     }
 
-    // Method to get the data from a single child canister in chunks
+    // Method to get a page of reports from the child canisters, each child only returns the reports up to the requested page
+    // and the parent merges them, the pages are the same as when all the reports would be fetched and paged
+    async fn get_paged_child_data(
+        limit: usize,
+        page: usize,
+        expression: ReportFilterExpression,
+        sorts: Vec<ReportSort>,
+    ) -> AggregatedResponse<PagedResponse<ReportResponse>> {
+        let top_reports = Self::get_top_child_data(ReportQuery {
+            expression,
+            sorts,
+            cursor: None,
+            limit: limit.saturating_mul(page),
+        })
+        .await;
+
+        top_reports.map(|top_reports| {
+            get_merged_paged_data(top_reports.data, top_reports.total, limit, page)
+        })
+    }
+
+    // Method to get the first reports of the query over all the child canisters
+    // the child canisters that could not be queried are returned in the failed children
+    async fn get_top_child_data(
+        query: ReportQuery,
+    ) -> AggregatedResponse<CursorPagedResponse<ReportResponse>> {
+        let group_identifier = query
+            .expression
+            .get_required_filters()
+            .into_iter()
+            .find_map(|filter| match filter {
+                ReportFilter::Group(group_identifier) => Some(*group_identifier),
+                _ => None,
+            });
+        let canisters = Self::get_child_canisters(group_identifier);
+
+        // Get the top reports from the child canisters concurrently, the reports of every child canister
+        // are merged as soon as they are received so the parent holds at most `limit` merged reports
+        // next to the reports of the child canisters that are being received
+        let mut results = stream::iter(canisters)
+            .map(|canister| {
+                let query = &query;
+                async move {
+                    let result = Self::get_filtered_child_data(canister, query).await;
+                    (canister, result)
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_CALLS);
+
+        let mut top_reports = CursorPagedResponse {
+            data: vec![],
+            limit: query.limit,
+            total: 0,
+            next_cursor: None,
+        };
+        let mut failed_children: Vec<FailedChild> = vec![];
+        while let Some((canister, result)) = results.next().await {
+            match result {
+                Ok(page) => {
+                    top_reports =
+                        merge_top_reports(vec![top_reports, page], &query.sorts, query.limit)
+                }
+                Err(err) => failed_children.push(FailedChild {
+                    canister,
+                    error: err.to_string(),
+                }),
+            }
+        }
+
+        AggregatedResponse::new(top_reports, failed_children)
+    }
+
+    // Method to get the top reports of the query from a single child canister in chunks
     // requires composite queries to be released to mainnet
     async fn get_filtered_child_data(
         canister_principal: Principal,
-        filters: &Vec<ReportFilter>,
-        filter_type: &FilterType,
-    ) -> Vec<ReportResponse> {
-        // Do initial fetch of the first chunk and determine the number of chunks
-        let (mut bytes, (_, last)) =
-            Self::get_chunked_child_data(canister_principal, filters, filter_type, 0, None).await;
-
-        // If there are more chunks, fetch them
-        if last > 1 {
-            for i in 1..last + 1 {
-                let (mut _bytes, _) =
-                    Self::get_chunked_child_data(canister_principal, filters, filter_type, i, None)
-                        .await;
-                // Append the bytes to the first chunk
-                bytes.append(&mut _bytes);
+        query: &ReportQuery,
+    ) -> Result<CursorPagedResponse<ReportResponse>, ApiError> {
+        let mut attempt = 1;
+        loop {
+            match Self::get_child_snapshot(canister_principal, query).await {
+                Ok(reports) => return Ok(reports),
+                // The data of the child canister changed while the chunks were fetched, start again with a new snapshot
+                Err(ApiError::BadRequest(err))
+                    if err.tag == "SNAPSHOT_EXPIRED" && attempt < MAX_SNAPSHOT_ATTEMPTS =>
+                {
+                    attempt += 1
+                }
+                Err(err) => return Err(err),
             }
         }
+    }
 
-        // Deserialize the bytes to the correct data type
-        match deserialize::<Vec<ReportResponse>>(bytes.clone()) {
-            Ok(_res) => _res,
-            Err(_err) => {
-                ic_cdk::println!("Error: {}", _err);
-                vec![]
-            }
+    // Method to get all the chunks of a snapshot from a child canister, every chunk holds the next ordered reports
+    // and is deserialized and appended when it is received, the chunks are verified with the hash of the snapshot.
+    // A child canister returns at most `limit` reports, so the reports held for a child are bounded by the limit
+    async fn get_child_snapshot(
+        canister_principal: Principal,
+        query: &ReportQuery,
+    ) -> Result<CursorPagedResponse<ReportResponse>, ApiError> {
+        // Do initial fetch of the first chunk to get the snapshot id and the number of chunks
+        let first_chunk = Self::get_chunked_child_data(canister_principal, query, None, 0).await?;
+        let snapshot_id = first_chunk.snapshot_id;
+
+        let mut verifier = ChunkVerifier::new(&first_chunk);
+        let mut reports = Self::read_chunk(canister_principal, &mut verifier, first_chunk)?;
+
+        // If there are more chunks, fetch them concurrently from the same snapshot
+        // the chunks are returned in the order they are requested so the reports stay in order
+        let mut next_chunks = stream::iter(1..verifier.chunk_count())
+            .map(|i| Self::get_chunked_child_data(canister_principal, query, Some(snapshot_id), i))
+            .buffered(MAX_CONCURRENT_CALLS);
+
+        while let Some(chunk) = next_chunks.next().await {
+            let mut chunk_reports = Self::read_chunk(canister_principal, &mut verifier, chunk?)?;
+            reports.data.append(&mut chunk_reports.data);
         }
+
+        verifier
+            .finish()
+            .map_err(|err| Self::snapshot_error(canister_principal, "INVALID_CHUNKS", err))?;
+        Ok(reports)
     }
 
-    // Method to get ordered reports
-    fn get_ordered_reports(
-        mut reports: Vec<ReportResponse>,
-        sort: ReportSort,
-    ) -> Vec<ReportResponse> {
-        use ReportSort::*;
-        match sort {
-            Id(direction) => match direction {
-                SortDirection::Asc => reports.sort_by(|a, b| {
-                    Identifier::decode(&a.identifier)
-                        .0
-                        .cmp(&Identifier::decode(&b.identifier).0)
-                }),
-                SortDirection::Desc => reports.sort_by(|a, b| {
-                    Identifier::decode(&b.identifier)
-                        .0
-                        .cmp(&Identifier::decode(&a.identifier).0)
-                }),
-            },
-            CreatedOn(direction) => match direction {
-                SortDirection::Asc => reports.sort_by(|a, b| a.created_on.cmp(&b.created_on)),
-                SortDirection::Desc => reports.sort_by(|a, b| b.created_on.cmp(&a.created_on)),
-            },
-            Kind(direction) => match direction {
-                SortDirection::Asc => reports.sort_by(|a, b| a.subject_kind.cmp(&b.subject_kind)),
-                SortDirection::Desc => reports.sort_by(|a, b| b.subject_kind.cmp(&a.subject_kind)),
-            },
-        };
-        reports
+    // Method to verify and deserialize a single chunk
+    fn read_chunk(
+        canister_principal: Principal,
+        verifier: &mut ChunkVerifier,
+        chunk: DataChunk,
+    ) -> Result<CursorPagedResponse<ReportResponse>, ApiError> {
+        verifier
+            .add(&chunk)
+            .map_err(|err| Self::snapshot_error(canister_principal, "INVALID_CHUNKS", err))?;
+
+        deserialize::<CursorPagedResponse<ReportResponse>>(chunk.bytes).map_err(|err| {
+            Self::snapshot_error(canister_principal, "FAILED_TO_DESERIALIZE", err.to_string())
+        })
     }
 
-    // Inter canister call to fetch the chunked data from the child canister
+    // Method to create the error of a snapshot that could not be read
+    fn snapshot_error(canister_principal: Principal, tag: &str, message: String) -> ApiError {
+        api_error(
+            ApiErrorType::Unexpected,
+            tag,
+            message.as_str(),
+            &Self::get_name(),
+            "get_child_snapshot",
+            Some(vec![format!("canister - {}", canister_principal)]),
+        )
+    }
+
+    // Inter canister call to fetch a chunk of a snapshot from the child canister
     async fn get_chunked_child_data(
         canister_principal: Principal,
-        filters: &Vec<ReportFilter>,
-        filter_type: &FilterType,
+        query: &ReportQuery,
+        snapshot_id: Option<u64>,
         chunk: usize,
-        max_bytes_per_chunk: Option<usize>,
-    ) -> (Vec<u8>, (usize, usize)) {
-        // If the max bytes per chunk is not provided, use the default of 2_000_000 (2mb)
-        let _max_bytes_per_chunk = max_bytes_per_chunk.unwrap_or(2_000_000);
-        let result: Result<(Vec<u8>, (usize, usize)), _> = call::call(
+    ) -> Result<DataChunk, ApiError> {
+        // The chunk size of the report config is used, or the max of 2_000_000 (2mb)
+        let max_bytes_per_chunk = Self::get_report_config()
+            .max_bytes_per_chunk
+            .unwrap_or(MAX_BYTES_PER_CHUNK);
+        let result: Result<(Result<DataChunk, ApiError>,), _> = call::call(
             canister_principal,
-            "get_chunked_data",
-            (filters, filter_type, chunk, _max_bytes_per_chunk),
+            "get_chunked_data_by_query",
+            (query, snapshot_id, chunk, max_bytes_per_chunk),
         )
         .await;
 
-        // return the bytes and the chunk info, if there is an error, return an empty vec and chunk info
+        // return the chunk, if the call is rejected return the rejection as error
         match result {
-            Ok(_res) => _res,
-            _ => (vec![], (0, 0)),
+            Ok((_res,)) => _res,
+            Err((code, message)) => Err(api_error(
+                ApiErrorType::Unexpected,
+                "CALL_REJECTED",
+                format!("{:?}: {}", code, message).as_str(),
+                &Self::get_name(),
+                "get_chunked_child_data",
+                Some(vec![format!("canister - {}", canister_principal)]),
+            )),
         }
     }
 
@@ -550,3 +1506,43 @@ impl ScalableData {
         DATA.with(|v| v.borrow().name.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spare_canister_is_not_created_without_the_child_wasm() {
+        assert_eq!(
+            SpareBackoff::default().next_install(false, None, 0),
+            SpareInstall::Wait
+        );
+        assert_eq!(
+            SpareBackoff::default().next_install(true, None, 0),
+            SpareInstall::Spawn
+        );
+    }
+
+    #[test]
+    fn failed_spare_install_is_retried_on_the_same_canister_after_a_backoff() {
+        let empty_canister = Principal::from_slice(&[1]);
+        let interval = CAPACITY_CHECK_INTERVAL.as_nanos() as u64;
+
+        let backoff = SpareBackoff::default().record_failure(0);
+        assert_eq!(
+            backoff.next_install(true, Some(empty_canister), interval),
+            SpareInstall::Wait
+        );
+        assert_eq!(
+            backoff.next_install(true, Some(empty_canister), 2 * interval),
+            SpareInstall::Retry(empty_canister)
+        );
+
+        // Every failure doubles the wait, up to the max exponent
+        let backoff = backoff.record_failure(2 * interval);
+        assert_eq!(backoff.retry_at, 6 * interval);
+
+        let backoff = (0..10).fold(backoff, |backoff, _| backoff.record_failure(0));
+        assert_eq!(backoff.retry_at, interval << MAX_SPARE_BACKOFF_EXPONENT);
+    }
+}
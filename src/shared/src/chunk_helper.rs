@@ -0,0 +1,181 @@
+use std::ops::Range;
+
+use sha2::{Digest, Sha256};
+
+use crate::report_model::DataChunk;
+
+// The max size of a chunk, keeps the response of a chunk below the 2 MiB message limit
+pub const MAX_BYTES_PER_CHUNK: usize = 2_000_000;
+
+// Get the sha256 hash of the bytes of all the chunks in order
+pub fn hash_chunks(chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    chunks.iter().for_each(|chunk| hasher.update(chunk));
+    hasher.finalize().to_vec()
+}
+
+// Split items in chunks at item boundaries, items are added to a chunk as long as the overhead of the chunk
+// and the sizes of the items fit in the max bytes per chunk, an item that does not fit in an empty chunk gets
+// a chunk of its own, no items are returned as a single empty chunk
+pub fn get_chunk_ranges(
+    item_sizes: &[usize],
+    overhead: usize,
+    max_bytes_per_chunk: usize,
+) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    let mut start = 0;
+    let mut size = overhead;
+
+    for (index, item_size) in item_sizes.iter().enumerate() {
+        if index > start && size + item_size > max_bytes_per_chunk {
+            ranges.push(start..index);
+            start = index;
+            size = overhead;
+        }
+        size += item_size;
+    }
+
+    ranges.push(start..item_sizes.len());
+    ranges
+}
+
+// Get a chunk of a snapshot, `None` if the chunk is out of range
+pub fn get_data_chunk(chunks: Vec<Vec<u8>>, snapshot_id: u64, chunk: usize) -> Option<DataChunk> {
+    let hash = hash_chunks(&chunks);
+    let total_bytes = chunks.iter().map(Vec::len).sum();
+    let chunk_count = chunks.len();
+
+    chunks.into_iter().nth(chunk).map(|bytes| DataChunk {
+        snapshot_id,
+        total_bytes,
+        chunk_count,
+        hash,
+        chunk,
+        bytes,
+    })
+}
+
+// Verifies the chunks of a snapshot while they are received in order, so the chunks can be processed
+// one by one without joining the bytes
+pub struct ChunkVerifier {
+    snapshot_id: u64,
+    total_bytes: usize,
+    chunk_count: usize,
+    hash: Vec<u8>,
+    next_chunk: usize,
+    received_bytes: usize,
+    hasher: Sha256,
+}
+
+impl ChunkVerifier {
+    // Start the verification with the header of the first chunk
+    pub fn new(first_chunk: &DataChunk) -> Self {
+        Self {
+            snapshot_id: first_chunk.snapshot_id,
+            total_bytes: first_chunk.total_bytes,
+            chunk_count: first_chunk.chunk_count,
+            hash: first_chunk.hash.clone(),
+            next_chunk: 0,
+            received_bytes: 0,
+            hasher: Sha256::new(),
+        }
+    }
+
+    // The number of chunks of the snapshot
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_count
+    }
+
+    // Add the next chunk, the chunk needs to be the next chunk of the same snapshot
+    pub fn add(&mut self, chunk: &DataChunk) -> Result<(), String> {
+        if chunk.snapshot_id != self.snapshot_id || chunk.chunk != self.next_chunk {
+            return Err(format!(
+                "Expected chunk {} of snapshot {}, received chunk {} of snapshot {}",
+                self.next_chunk, self.snapshot_id, chunk.chunk, chunk.snapshot_id
+            ));
+        }
+
+        self.hasher.update(&chunk.bytes);
+        self.received_bytes += chunk.bytes.len();
+        self.next_chunk += 1;
+        Ok(())
+    }
+
+    // Check that all the chunks are received and match the length and hash of the snapshot
+    pub fn finish(self) -> Result<(), String> {
+        if self.next_chunk != self.chunk_count {
+            return Err(format!(
+                "Received {} of the {} chunks",
+                self.next_chunk, self.chunk_count
+            ));
+        }
+
+        if self.received_bytes != self.total_bytes || self.hasher.finalize().to_vec() != self.hash {
+            return Err(format!(
+                "The chunks of snapshot {} do not match the hash",
+                self.snapshot_id
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_chunks(chunks: &[Vec<u8>]) -> Vec<DataChunk> {
+        (0..chunks.len())
+            .filter_map(|chunk| get_data_chunk(chunks.to_vec(), 1, chunk))
+            .collect()
+    }
+
+    fn verify(chunks: &[DataChunk]) -> Result<(), String> {
+        let mut verifier = ChunkVerifier::new(&chunks[0]);
+        for chunk in chunks {
+            verifier.add(chunk)?;
+        }
+        verifier.finish()
+    }
+
+    #[test]
+    fn chunks_are_cut_at_item_boundaries() {
+        // Overhead of 2 bytes per chunk and a max of 10 bytes per chunk
+        assert_eq!(
+            get_chunk_ranges(&[3, 3, 3, 4, 8, 12, 1], 2, 10),
+            vec![0..2, 2..4, 4..5, 5..6, 6..7]
+        );
+        assert_eq!(get_chunk_ranges(&[4, 4], 2, 10), vec![0..2]);
+        assert_eq!(get_chunk_ranges(&[], 2, 10), vec![0..0]);
+    }
+
+    #[test]
+    fn chunks_are_verified() {
+        let bytes = vec![vec![1, 2, 3], vec![4, 5], vec![6]];
+        assert!(verify(&get_chunks(&bytes)).is_ok());
+        assert!(get_data_chunk(bytes.clone(), 1, 3).is_none());
+
+        // A chunk of another snapshot
+        let mut chunks = get_chunks(&bytes);
+        chunks[1].snapshot_id = 2;
+        assert!(verify(&chunks).is_err());
+
+        // A chunk that is cut from changed data
+        let mut changed = bytes.clone();
+        changed[1] = vec![4, 0];
+        let mut chunks = get_chunks(&bytes);
+        chunks[1] = get_data_chunk(changed, 1, 1).unwrap();
+        assert!(verify(&chunks).is_err());
+
+        // Chunks out of order
+        let mut chunks = get_chunks(&bytes);
+        chunks.swap(1, 2);
+        assert!(verify(&chunks).is_err());
+
+        // A missing chunk
+        let mut chunks = get_chunks(&bytes);
+        chunks.pop();
+        assert!(verify(&chunks).is_err());
+    }
+}
@@ -1 +1,7 @@
+pub mod chunk_helper;
+pub mod permission_helper;
+pub mod query_engine;
 pub mod report_model;
+#[cfg(test)]
+mod test_helper;
+pub mod validate_helper;
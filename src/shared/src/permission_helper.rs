@@ -0,0 +1,87 @@
+use candid::Principal;
+use ic_scalable_misc::{
+    enums::api_error_type::{ApiError, ApiErrorType},
+    helpers::{
+        error_helper::api_error,
+        role_helper::{default_roles, get_group_roles, get_member_roles},
+    },
+    models::group_role::GroupRole,
+};
+
+use crate::report_model::{ReportAction, ReportPolicy};
+
+// Fetch the roles of the member and the group, the caller needs to be the principal of the member
+// the name is the name of the calling canister and is used for error handling
+pub async fn get_roles(
+    caller: Principal,
+    group_identifier: Principal,
+    member_identifier: Principal,
+    name: &str,
+) -> Result<(Vec<String>, Vec<GroupRole>), ApiError> {
+    let group_roles = get_group_roles(group_identifier).await;
+    let member_roles = get_member_roles(member_identifier, group_identifier).await;
+
+    match member_roles {
+        Ok((_principal, _roles)) => {
+            if caller != _principal {
+                return Err(api_error(
+                    ApiErrorType::Unauthorized,
+                    "PRINCIPAL_MISMATCH",
+                    "Principal mismatch",
+                    name,
+                    "check_permission",
+                    None,
+                ));
+            }
+
+            match group_roles {
+                Ok(mut _group_roles) => {
+                    _group_roles.append(&mut default_roles());
+                    Ok((_roles, _group_roles))
+                }
+                Err(err) => Err(api_error(
+                    ApiErrorType::Unauthorized,
+                    "NO_PERMISSION",
+                    err.as_str(),
+                    name,
+                    "check_permission",
+                    None,
+                )),
+            }
+        }
+        Err(err) => Err(api_error(
+            ApiErrorType::Unauthorized,
+            "NO_PERMISSION",
+            err.as_str(),
+            name,
+            "check_permission",
+            None,
+        )),
+    }
+}
+
+// Check if the caller is allowed to perform the report action in the group according to the policy
+pub async fn check_report_permission(
+    caller: Principal,
+    group_identifier: Principal,
+    member_identifier: Principal,
+    policy: &ReportPolicy,
+    action: ReportAction,
+    name: &str,
+) -> Result<Principal, ApiError> {
+    let (member_roles, group_roles) =
+        get_roles(caller, group_identifier, member_identifier, name).await?;
+
+    if !policy.is_allowed(&action, &member_roles, &group_roles) {
+        return Err(api_error(
+            ApiErrorType::Unauthorized,
+            "NO_PERMISSION",
+            "No permission",
+            name,
+            "check_report_permission",
+            Some(vec![format!("action - {:?}", &action)]),
+        ));
+    }
+
+    Ok(caller)
+}
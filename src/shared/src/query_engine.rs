@@ -0,0 +1,677 @@
+use std::cmp::Ordering;
+
+use candid::{Decode, Encode, Principal};
+use ic_scalable_misc::{
+    enums::{filter_type::FilterType, sort_type::SortDirection},
+    helpers::paging_helper::get_paged_data,
+    models::{identifier_model::Identifier, paged_response_models::PagedResponse},
+};
+
+use crate::report_model::{
+    CursorPagedResponse, ReportCursor, ReportFilter, ReportFilterExpression, ReportQuery,
+    ReportReason, ReportResponse, ReportSort, ReportSortKey, ReportStatus,
+};
+
+// Query engine for reports, the child and parent canisters both use these methods
+// so the filters, sorts and pages behave the same on both sides
+
+// Get the reports that match the filter expression, sorted and paged
+pub fn query_reports(
+    reports: Vec<ReportResponse>,
+    expression: &ReportFilterExpression,
+    sorts: &[ReportSort],
+    limit: usize,
+    page: usize,
+) -> PagedResponse<ReportResponse> {
+    let ordered_reports = order_reports(filter_reports(reports, expression), sorts);
+    get_paged_data(ordered_reports, limit, page)
+}
+
+// Get the reports that match the filter expression, the order of the reports is kept
+pub fn filter_reports(
+    mut reports: Vec<ReportResponse>,
+    expression: &ReportFilterExpression,
+) -> Vec<ReportResponse> {
+    reports.retain(|report| expression.matches(report));
+    reports
+}
+
+// Sort the reports, the sorts are applied in order and the next sort is only used when the previous sort values are equal
+pub fn order_reports(
+    mut reports: Vec<ReportResponse>,
+    sorts: &[ReportSort],
+) -> Vec<ReportResponse> {
+    reports.sort_by(|a, b| compare_reports(sorts, a, b));
+    reports
+}
+
+// Get the first reports of the query without sorting all the reports, only the reports that are returned are sorted
+// the response is the same as `get_cursor_paged_data` on the ordered reports
+pub fn get_top_reports(
+    reports: Vec<ReportResponse>,
+    query: &ReportQuery,
+) -> CursorPagedResponse<ReportResponse> {
+    let total = reports.len();
+    let mut reports = match &query.cursor {
+        None => reports,
+        Some(cursor) => reports
+            .into_iter()
+            .filter(|report| cursor.is_before(&query.sorts, report))
+            .collect(),
+    };
+
+    let has_more = reports.len() > query.limit;
+    if has_more {
+        // Move the first `limit` reports to the front, in no particular order
+        reports.select_nth_unstable_by(query.limit, |a, b| compare_reports(&query.sorts, a, b));
+        reports.truncate(query.limit);
+    }
+
+    let data = order_reports(reports, &query.sorts);
+    let next_cursor = match (has_more, data.last()) {
+        (true, Some(last)) => Some(ReportCursor::new(&query.sorts, last).encode()),
+        _ => None,
+    };
+
+    CursorPagedResponse {
+        data,
+        limit: query.limit,
+        total,
+        next_cursor,
+    }
+}
+
+// Merge the top reports of multiple canisters into the first `limit` reports over all the canisters,
+// the total is the sum of the totals and there is a next cursor if any of the canisters has more reports
+pub fn merge_top_reports(
+    pages: Vec<CursorPagedResponse<ReportResponse>>,
+    sorts: &[ReportSort],
+    limit: usize,
+) -> CursorPagedResponse<ReportResponse> {
+    let total = pages.iter().map(|page| page.total).sum();
+    let mut has_more = pages.iter().any(|page| page.next_cursor.is_some());
+
+    let mut data = merge_ordered_reports(pages.into_iter().map(|page| page.data).collect(), sorts);
+    if data.len() > limit {
+        has_more = true;
+        data.truncate(limit);
+    }
+
+    let next_cursor = match (has_more, data.last()) {
+        (true, Some(last)) => Some(ReportCursor::new(sorts, last).encode()),
+        _ => None,
+    };
+
+    CursorPagedResponse {
+        data,
+        limit,
+        total,
+        next_cursor,
+    }
+}
+
+// K-way merge of lists of reports that are each already ordered by the sorts
+pub fn merge_ordered_reports(
+    lists: Vec<Vec<ReportResponse>>,
+    sorts: &[ReportSort],
+) -> Vec<ReportResponse> {
+    let mut merged: Vec<ReportResponse> = Vec::with_capacity(lists.iter().map(Vec::len).sum());
+    let mut lists: Vec<_> = lists
+        .into_iter()
+        .map(|list| list.into_iter().peekable())
+        .collect();
+
+    loop {
+        // Take the report from the list with the first report in the order of the sorts
+        let next = lists
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, list)| list.peek().map(|report| (index, report)))
+            .min_by(|a, b| compare_reports(sorts, a.1, b.1))
+            .map(|(index, _)| index);
+
+        match next.and_then(|index| lists[index].next()) {
+            Some(report) => merged.push(report),
+            None => return merged,
+        }
+    }
+}
+
+// Page the merged top reports, `total` is the number of matching reports over all the canisters
+// the pages are calculated the same way as `get_paged_data` so the pages are the same as when all the reports are paged
+pub fn get_merged_paged_data(
+    top_reports: Vec<ReportResponse>,
+    total: usize,
+    limit: usize,
+    page: usize,
+) -> PagedResponse<ReportResponse> {
+    if page == 0 {
+        return PagedResponse {
+            page,
+            limit,
+            total,
+            number_of_pages: 0,
+            data: vec![],
+        };
+    }
+
+    let limit = limit.min(total);
+    if limit == 0 {
+        return PagedResponse {
+            page: 0,
+            limit,
+            total,
+            number_of_pages: 0,
+            data: vec![],
+        };
+    }
+
+    let number_of_pages = total.div_ceil(limit);
+    let (page, start, end) = match page >= number_of_pages {
+        true => (number_of_pages, number_of_pages * limit - limit, total),
+        false => (page, (page - 1) * limit, page * limit),
+    };
+
+    PagedResponse {
+        page,
+        limit,
+        total,
+        number_of_pages,
+        data: top_reports
+            .into_iter()
+            .skip(start)
+            .take(end - start)
+            .collect(),
+    }
+}
+
+// Get the page of ordered reports that starts after the cursor
+pub fn get_cursor_paged_data(
+    ordered_reports: Vec<ReportResponse>,
+    sorts: &[ReportSort],
+    cursor: Option<ReportCursor>,
+    limit: usize,
+) -> CursorPagedResponse<ReportResponse> {
+    let total = ordered_reports.len();
+    let mut remaining = ordered_reports
+        .into_iter()
+        .filter(|report| cursor.as_ref().is_none_or(|c| c.is_before(sorts, report)))
+        .peekable();
+
+    let data: Vec<ReportResponse> = remaining.by_ref().take(limit).collect();
+    let next_cursor = match (remaining.peek(), data.last()) {
+        (Some(_), Some(last)) => Some(ReportCursor::new(sorts, last).encode()),
+        _ => None,
+    };
+
+    CursorPagedResponse {
+        data,
+        limit,
+        total,
+        next_cursor,
+    }
+}
+
+// Compare two reports on a list of sorts, the next sort is only used when the previous sort values are equal
+// and reports with the same sort values are ordered by identifier so the order is always the same
+pub fn compare_reports(sorts: &[ReportSort], a: &ReportResponse, b: &ReportResponse) -> Ordering {
+    compare_sort_keys(
+        sorts,
+        &get_sort_keys(sorts, a),
+        &a.identifier,
+        &get_sort_keys(sorts, b),
+        &b.identifier,
+    )
+}
+
+fn get_sort_keys(sorts: &[ReportSort], report: &ReportResponse) -> Vec<ReportSortKey> {
+    sorts.iter().map(|sort| sort.get_key(report)).collect()
+}
+
+fn compare_sort_keys(
+    sorts: &[ReportSort],
+    keys_a: &[ReportSortKey],
+    identifier_a: &Principal,
+    keys_b: &[ReportSortKey],
+    identifier_b: &Principal,
+) -> Ordering {
+    sorts
+        .iter()
+        .zip(keys_a.iter().zip(keys_b.iter()))
+        .map(|(sort, (key_a, key_b))| sort.compare_keys(key_a, key_b))
+        .find(|ordering| ordering != &Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| identifier_a.cmp(identifier_b))
+}
+
+impl ReportSort {
+    // Get the value of the sort field of a report
+    pub fn get_key(&self, report: &ReportResponse) -> ReportSortKey {
+        use ReportSort::*;
+        match self {
+            Id(_) => ReportSortKey::Id(Identifier::decode(&report.identifier).0),
+            Kind(_) => ReportSortKey::Kind(report.subject_kind.clone()),
+            CreatedOn(_) => ReportSortKey::CreatedOn(report.created_on),
+            Reason(_) => ReportSortKey::Reason(report.reason.clone()),
+            Status(_) => ReportSortKey::Status(report.status.clone()),
+        }
+    }
+
+    // Get an empty key of the sort field, used to check if a cursor matches the sort
+    fn get_key_kind(&self) -> ReportSortKey {
+        use ReportSort::*;
+        match self {
+            Id(_) => ReportSortKey::Id(0),
+            Kind(_) => ReportSortKey::Kind(String::default()),
+            CreatedOn(_) => ReportSortKey::CreatedOn(0),
+            Reason(_) => ReportSortKey::Reason(ReportReason::default()),
+            Status(_) => ReportSortKey::Status(ReportStatus::default()),
+        }
+    }
+
+    fn get_direction(&self) -> &SortDirection {
+        use ReportSort::*;
+        match self {
+            Id(direction) | Kind(direction) | CreatedOn(direction) | Reason(direction)
+            | Status(direction) => direction,
+        }
+    }
+
+    // Compare two sort values in the direction of this sort
+    fn compare_keys(&self, key_a: &ReportSortKey, key_b: &ReportSortKey) -> Ordering {
+        match self.get_direction() {
+            SortDirection::Asc => key_a.cmp(key_b),
+            SortDirection::Desc => key_b.cmp(key_a),
+        }
+    }
+}
+
+impl ReportCursor {
+    pub fn new(sorts: &[ReportSort], report: &ReportResponse) -> Self {
+        Self {
+            keys: get_sort_keys(sorts, report),
+            identifier: report.identifier,
+        }
+    }
+
+    // Encode the cursor to an opaque hex string
+    pub fn encode(&self) -> String {
+        Encode!(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    // Decode a cursor, the cursor has to be created with the same sort fields
+    pub fn decode(cursor: &str, sorts: &[ReportSort]) -> Result<Self, String> {
+        if !cursor.len().is_multiple_of(2) {
+            return Err("Invalid cursor".to_string());
+        }
+
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(cursor.get(i..i + 2).unwrap_or_default(), 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| "Invalid cursor".to_string())?;
+
+        let cursor = Decode!(&bytes, Self).map_err(|_| "Invalid cursor".to_string())?;
+        let matches_sorts = cursor.keys.len() == sorts.len()
+            && cursor.keys.iter().zip(sorts.iter()).all(|(key, sort)| {
+                std::mem::discriminant(key) == std::mem::discriminant(&sort.get_key_kind())
+            });
+        if !matches_sorts {
+            return Err("Cursor does not match the sort".to_string());
+        }
+        Ok(cursor)
+    }
+
+    // Check if a report comes after the cursor in the order of the sorts
+    pub fn is_before(&self, sorts: &[ReportSort], report: &ReportResponse) -> bool {
+        compare_sort_keys(
+            sorts,
+            &get_sort_keys(sorts, report),
+            &report.identifier,
+            &self.keys,
+            &self.identifier,
+        ) == Ordering::Greater
+    }
+}
+
+impl ReportFilter {
+    // Check if a report matches the filter
+    pub fn matches(&self, report: &ReportResponse) -> bool {
+        use ReportFilter::*;
+        match self {
+            Kind(value) => report.subject_kind.contains(value),
+            CreatedOn(value) => {
+                if value.end_date > 0 {
+                    report.created_on >= value.start_date && report.created_on <= value.end_date
+                } else {
+                    report.created_on >= value.start_date
+                }
+            }
+            ReportedBy(value) => &report.reported_by == value,
+            Status(value) => &report.status == value,
+            Reason(value) => &report.reason == value,
+            MessageContains(value) => report
+                .message
+                .to_lowercase()
+                .contains(&value.to_lowercase()),
+            Subject(value) => &report.subject == value,
+            Group(value) => &report.group_identifier == value,
+        }
+    }
+}
+
+impl ReportFilterExpression {
+    // Convert a flat list of filters combined with a filter type to an expression
+    pub fn from_filters(filters: Vec<ReportFilter>, filter_type: FilterType) -> Self {
+        let expressions = filters.into_iter().map(Self::Filter).collect();
+        match filter_type {
+            FilterType::And => Self::And(expressions),
+            FilterType::Or => Self::Or(expressions),
+        }
+    }
+
+    // Combine the expression with a filter that also has to match, if one is given
+    pub fn and(self, filter: Option<ReportFilter>) -> Self {
+        match filter {
+            None => self,
+            Some(filter) => Self::And(vec![self, Self::Filter(filter)]),
+        }
+    }
+
+    // Check if a report matches the expression
+    pub fn matches(&self, report: &ReportResponse) -> bool {
+        use ReportFilterExpression::*;
+        match self {
+            Filter(filter) => filter.matches(report),
+            And(expressions) => expressions.iter().all(|e| e.matches(report)),
+            Or(expressions) => expressions.iter().any(|e| e.matches(report)),
+            Not(expression) => !expression.matches(report),
+        }
+    }
+
+    // Get the filters that every matching report has to match, used to narrow the reports with an index
+    pub fn get_required_filters(&self) -> Vec<&ReportFilter> {
+        use ReportFilterExpression::*;
+        match self {
+            Filter(filter) => vec![filter],
+            And(expressions) => expressions
+                .iter()
+                .flat_map(|e| e.get_required_filters())
+                .collect(),
+            Or(_) | Not(_) => vec![],
+        }
+    }
+}
+
+// Split a message in lowercase alphanumeric tokens, used for the message search index
+pub fn tokenize_message(message: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = message
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_string())
+        .collect();
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helper::report;
+    use ic_scalable_misc::models::date_models::DateRange;
+
+    #[test]
+    fn cursor_pages_follow_the_sort_order() {
+        let sorts = vec![ReportSort::CreatedOn(SortDirection::Desc)];
+        let mut reports: Vec<ReportResponse> = (0..5).map(|id| report(id, id % 2)).collect();
+        reports.sort_by(|a, b| compare_reports(&sorts, a, b));
+
+        let first = get_cursor_paged_data(reports.clone(), &sorts, None, 2);
+        let cursor = ReportCursor::decode(first.next_cursor.as_ref().unwrap(), &sorts).unwrap();
+        let second = get_cursor_paged_data(reports.clone(), &sorts, Some(cursor), 10);
+
+        assert_eq!(first.total, 5);
+        assert_eq!(second.next_cursor, None);
+        let paged: Vec<Principal> = first
+            .data
+            .iter()
+            .chain(second.data.iter())
+            .map(|r| r.identifier)
+            .collect();
+        let expected: Vec<Principal> = reports.iter().map(|r| r.identifier).collect();
+        assert_eq!(paged, expected);
+    }
+
+    #[test]
+    fn cursor_must_match_the_sort() {
+        let report = report(1, 1);
+        let cursor = ReportCursor::new(&[ReportSort::Id(SortDirection::Asc)], &report).encode();
+
+        assert!(ReportCursor::decode(&cursor, &[ReportSort::Id(SortDirection::Desc)]).is_ok());
+        assert!(
+            ReportCursor::decode(&cursor, &[ReportSort::CreatedOn(SortDirection::Asc)]).is_err()
+        );
+        assert!(ReportCursor::decode(&cursor, &[]).is_err());
+        assert!(ReportCursor::decode("zz", &[ReportSort::Id(SortDirection::Asc)]).is_err());
+    }
+
+    #[test]
+    fn later_sorts_break_ties_of_earlier_sorts() {
+        let mut reports = [report(1, 1), report(2, 2), report(3, 1)];
+        reports[2].status = ReportStatus::InReview;
+
+        // status asc, created_on desc, id asc
+        let sorts = vec![
+            ReportSort::Status(SortDirection::Asc),
+            ReportSort::CreatedOn(SortDirection::Desc),
+            ReportSort::Id(SortDirection::Asc),
+        ];
+        reports.sort_by(|a, b| compare_reports(&sorts, a, b));
+
+        let ids: Vec<u64> = reports
+            .iter()
+            .map(|r| Identifier::decode(&r.identifier).0)
+            .collect();
+        assert_eq!(ids, vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn nested_expressions_are_evaluated() {
+        let report = report(1, 1);
+        let other = Principal::management_canister();
+
+        // kind = mbr AND (status = open OR reported_by = other)
+        let expression = ReportFilterExpression::And(vec![
+            ReportFilterExpression::Filter(ReportFilter::Kind("mbr".to_string())),
+            ReportFilterExpression::Or(vec![
+                ReportFilterExpression::Filter(ReportFilter::Status(ReportStatus::Open)),
+                ReportFilterExpression::Filter(ReportFilter::ReportedBy(other)),
+            ]),
+        ]);
+        assert!(expression.matches(&report));
+        assert!(!ReportFilterExpression::Not(Box::new(expression.clone())).matches(&report));
+        assert_eq!(expression.get_required_filters().len(), 1);
+
+        assert!(ReportFilterExpression::from_filters(vec![], FilterType::And).matches(&report));
+        assert!(!ReportFilterExpression::from_filters(vec![], FilterType::Or).matches(&report));
+    }
+
+    #[test]
+    fn flat_filters_match_every_variant() {
+        let mut report = report(1, 10);
+        report.message = "Visit SCAM.example.com now".to_string();
+        let other = Principal::management_canister();
+
+        let matching = [
+            ReportFilter::Kind("mbr".to_string()),
+            ReportFilter::CreatedOn(DateRange {
+                start_date: 5,
+                end_date: 10,
+            }),
+            ReportFilter::ReportedBy(Principal::anonymous()),
+            ReportFilter::Status(ReportStatus::Open),
+            ReportFilter::Reason(ReportReason::Other),
+            ReportFilter::MessageContains("scam.EXAMPLE".to_string()),
+            ReportFilter::Subject(Principal::anonymous()),
+            ReportFilter::Group(Principal::anonymous()),
+        ];
+        for filter in matching.iter() {
+            assert!(filter.matches(&report), "{:?}", filter);
+        }
+
+        let not_matching = [
+            ReportFilter::Kind("grp".to_string()),
+            ReportFilter::CreatedOn(DateRange {
+                start_date: 11,
+                end_date: 0,
+            }),
+            ReportFilter::ReportedBy(other),
+            ReportFilter::Status(ReportStatus::Resolved),
+            ReportFilter::Reason(ReportReason::Spam),
+            ReportFilter::MessageContains("phishing".to_string()),
+            ReportFilter::Subject(other),
+            ReportFilter::Group(other),
+        ];
+        for filter in not_matching.iter() {
+            assert!(!filter.matches(&report), "{:?}", filter);
+        }
+    }
+
+    // Reports of three child canisters, some with the same created on
+    fn child_reports() -> Vec<Vec<ReportResponse>> {
+        vec![
+            vec![report(1, 5), report(2, 9), report(3, 1)],
+            vec![report(4, 5), report(5, 2), report(6, 7), report(7, 7)],
+            vec![report(8, 3)],
+        ]
+    }
+
+    fn identifiers(reports: &[ReportResponse]) -> Vec<u64> {
+        reports
+            .iter()
+            .map(|r| Identifier::decode(&r.identifier).0)
+            .collect()
+    }
+
+    #[test]
+    fn merged_top_reports_page_like_a_single_canister() {
+        let sorts = [ReportSort::CreatedOn(SortDirection::Desc)];
+        let all_reports = order_reports(child_reports().concat(), &sorts);
+
+        for page in 0..6 {
+            let query = ReportQuery {
+                expression: ReportFilterExpression::And(vec![]),
+                sorts: sorts.to_vec(),
+                cursor: None,
+                limit: page * 3,
+            };
+            let pages = child_reports()
+                .into_iter()
+                .map(|reports| get_top_reports(reports, &query))
+                .collect();
+            let merged = merge_top_reports(pages, &sorts, query.limit);
+
+            let expected = get_paged_data(all_reports.clone(), 3, page);
+            let actual = get_merged_paged_data(merged.data, merged.total, 3, page);
+            assert_eq!(actual.page, expected.page);
+            assert_eq!(actual.total, expected.total);
+            assert_eq!(actual.number_of_pages, expected.number_of_pages);
+            assert_eq!(identifiers(&actual.data), identifiers(&expected.data));
+        }
+    }
+
+    #[test]
+    fn merging_child_pages_one_by_one_matches_merging_all() {
+        let sorts = [ReportSort::CreatedOn(SortDirection::Desc)];
+        let query = ReportQuery {
+            expression: ReportFilterExpression::And(vec![]),
+            sorts: sorts.to_vec(),
+            cursor: None,
+            limit: 4,
+        };
+        let pages: Vec<CursorPagedResponse<ReportResponse>> = child_reports()
+            .into_iter()
+            .map(|reports| get_top_reports(reports, &query))
+            .collect();
+
+        let merged = merge_top_reports(pages.clone(), &sorts, query.limit);
+        let folded = pages.into_iter().fold(
+            CursorPagedResponse {
+                data: vec![],
+                limit: query.limit,
+                total: 0,
+                next_cursor: None,
+            },
+            |top_reports, page| merge_top_reports(vec![top_reports, page], &sorts, query.limit),
+        );
+
+        assert_eq!(folded.total, merged.total);
+        assert_eq!(folded.next_cursor, merged.next_cursor);
+        assert_eq!(identifiers(&folded.data), identifiers(&merged.data));
+    }
+
+    #[test]
+    fn merged_cursor_pages_follow_the_sort_order() {
+        let sorts = [ReportSort::CreatedOn(SortDirection::Asc)];
+        let mut query = ReportQuery {
+            expression: ReportFilterExpression::And(vec![]),
+            sorts: sorts.to_vec(),
+            cursor: None,
+            limit: 3,
+        };
+
+        let mut collected: Vec<ReportResponse> = vec![];
+        loop {
+            let pages = child_reports()
+                .into_iter()
+                .map(|reports| get_top_reports(reports, &query))
+                .collect();
+            let merged = merge_top_reports(pages, &sorts, query.limit);
+            assert_eq!(merged.total, 8);
+            collected.extend(merged.data);
+
+            match merged.next_cursor {
+                Some(cursor) => query.cursor = Some(ReportCursor::decode(&cursor, &sorts).unwrap()),
+                None => break,
+            }
+        }
+
+        let expected = order_reports(child_reports().concat(), &sorts);
+        assert_eq!(identifiers(&collected), identifiers(&expected));
+    }
+
+    #[test]
+    fn query_filters_sorts_and_pages() {
+        let mut reports: Vec<ReportResponse> = (0..6).map(|id| report(id, id)).collect();
+        reports[4].status = ReportStatus::Dismissed;
+
+        let expression = ReportFilterExpression::Not(Box::new(ReportFilterExpression::Filter(
+            ReportFilter::Status(ReportStatus::Dismissed),
+        )));
+        let page = query_reports(
+            reports,
+            &expression,
+            &[ReportSort::CreatedOn(SortDirection::Desc)],
+            2,
+            1,
+        );
+
+        assert_eq!(page.total, 5);
+        let created_on: Vec<u64> = page.data.iter().map(|r| r.created_on).collect();
+        assert_eq!(created_on, vec![5, 3]);
+    }
+
+    #[test]
+    fn message_tokens_are_lowercase_and_unique() {
+        assert_eq!(
+            tokenize_message("Scam at scam.example.com, SCAM!"),
+            vec!["at", "com", "example", "scam"]
+        );
+    }
+}
@@ -1,8 +1,14 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap};
 
 use candid::{CandidType, Decode, Deserialize, Encode, Principal};
 use ic_scalable_misc::{
-    enums::sort_type::SortDirection, models::date_models::DateRange,
+    enums::sort_type::SortDirection,
+    helpers::role_helper::has_permission,
+    models::{
+        date_models::DateRange,
+        group_role::GroupRole,
+        permissions_models::{PermissionActionType, PermissionType},
+    },
     traits::stable_storage_trait::StableStorableTrait,
 };
 use ic_stable_structures::{storable::Bound, Storable};
@@ -15,12 +21,18 @@ pub struct Report {
     pub group_identifier: Principal,
     pub message: String,
     pub created_on: u64,
+    // Optional to stay compatible with reports stored before the status was introduced
+    pub status: Option<ReportStatus>,
+    // Optional to stay compatible with reports stored before the reason was introduced
+    pub reason: Option<ReportReason>,
+    // Set when the report is deleted or retracted, the entry is kept as a tombstone
+    pub removed: Option<ReportRemoval>,
 }
 
 impl StableStorableTrait for Report {}
 
 impl Storable for Report {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
 
@@ -39,13 +51,33 @@ impl Default for Report {
             group_identifier: Principal::anonymous(),
             message: Default::default(),
             created_on: Default::default(),
+            status: Default::default(),
+            reason: Default::default(),
+            removed: Default::default(),
         }
     }
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ReportRemoval {
+    pub removed_by: Principal,
+    pub removed_on: u64,
+    pub kind: RemovalKind,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RemovalKind {
+    // Removed by a group moderator
+    Deleted,
+    // Withdrawn by the principal that filed the report
+    Retracted,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
 pub struct PostReport {
     pub subject: Principal,
+    // Optional so clients that do not send a reason keep working, defaults to `Other`
+    pub reason: Option<ReportReason>,
     pub message: String,
     pub group_identifier: Principal,
 }
@@ -59,6 +91,341 @@ pub struct ReportResponse {
     pub subject_kind: String,
     pub message: String,
     pub created_on: u64,
+    pub status: ReportStatus,
+    pub reason: ReportReason,
+}
+
+#[derive(
+    Clone, Debug, Default, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum ReportStatus {
+    #[default]
+    Open,
+    InReview,
+    Resolved,
+    Dismissed,
+}
+
+impl ReportStatus {
+    // Allowed transitions: Open -> InReview -> Resolved / Dismissed, closed reports can be reopened
+    pub fn can_transition_to(&self, status: &ReportStatus) -> bool {
+        use ReportStatus::*;
+        matches!(
+            (self, status),
+            (Open, InReview)
+                | (InReview, Resolved)
+                | (InReview, Dismissed)
+                | (Resolved, Open)
+                | (Dismissed, Open)
+        )
+    }
+}
+
+#[derive(
+    Clone, Debug, Default, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum ReportReason {
+    Spam,
+    Harassment,
+    Impersonation,
+    IllegalContent,
+    OffTopic,
+    #[default]
+    Other,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ReportNote {
+    pub author: Principal,
+    pub text: String,
+    pub resolution: Option<ResolutionAction>,
+    pub created_on: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct PostReportNote {
+    pub text: String,
+    pub resolution: Option<ResolutionAction>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ResolutionAction {
+    NoAction,
+    Warned,
+    ContentRemoved,
+    MemberRemoved,
+}
+
+// Append-only list of moderator notes attached to a single report
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+pub struct ReportNotes(pub Vec<ReportNote>);
+
+impl Storable for ReportNotes {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ReportAction {
+    Submit,
+    ViewOwn,
+    ViewAll,
+    Annotate,
+    Resolve,
+    Delete,
+}
+
+// Per group mapping of report actions to the group permission a member needs
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ReportPolicy {
+    pub submit: PermissionActionType,
+    pub view_own: PermissionActionType,
+    pub view_all: PermissionActionType,
+    pub annotate: PermissionActionType,
+    pub resolve: PermissionActionType,
+    pub delete: PermissionActionType,
+}
+
+impl Default for ReportPolicy {
+    // Members can submit and view their own reports, viewing all the reports of the group is a moderation action
+    fn default() -> Self {
+        Self {
+            submit: PermissionActionType::Read,
+            view_own: PermissionActionType::Read,
+            view_all: PermissionActionType::Edit,
+            annotate: PermissionActionType::Edit,
+            resolve: PermissionActionType::Edit,
+            delete: PermissionActionType::Delete,
+        }
+    }
+}
+
+impl Storable for ReportPolicy {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl ReportPolicy {
+    // Get the group permission that is required for the action
+    pub fn permission(&self, action: &ReportAction) -> PermissionActionType {
+        use ReportAction::*;
+        match action {
+            Submit => self.submit.clone(),
+            ViewOwn => self.view_own.clone(),
+            ViewAll => self.view_all.clone(),
+            Annotate => self.annotate.clone(),
+            Resolve => self.resolve.clone(),
+            Delete => self.delete.clone(),
+        }
+    }
+
+    // Check if a member with the given roles is allowed to perform the action
+    pub fn is_allowed(
+        &self,
+        action: &ReportAction,
+        member_roles: &Vec<String>,
+        group_roles: &Vec<GroupRole>,
+    ) -> bool {
+        has_permission(
+            member_roles,
+            &PermissionType::Group(None),
+            group_roles,
+            &self.permission(action),
+        )
+    }
+}
+
+// All reports about the same subject in a group merged into a single case
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ReportCase {
+    pub subject: Principal,
+    pub subject_kind: String,
+    pub group_identifier: Principal,
+    pub report_count: u64,
+    pub reporters: Vec<Principal>,
+    pub report_identifiers: Vec<Principal>,
+    pub first_reported_on: u64,
+    pub last_reported_on: u64,
+}
+
+impl ReportCase {
+    // Merge the reports per subject, cases with the most reports come first
+    pub fn from_reports(reports: Vec<ReportResponse>) -> Vec<ReportCase> {
+        let mut cases: BTreeMap<Principal, ReportCase> = BTreeMap::new();
+        for report in reports {
+            let case = cases.entry(report.subject).or_insert_with(|| ReportCase {
+                subject: report.subject,
+                subject_kind: report.subject_kind.clone(),
+                group_identifier: report.group_identifier,
+                report_count: 0,
+                reporters: vec![],
+                report_identifiers: vec![],
+                first_reported_on: report.created_on,
+                last_reported_on: report.created_on,
+            });
+
+            case.report_count += 1;
+            if !case.reporters.contains(&report.reported_by) {
+                case.reporters.push(report.reported_by);
+            }
+            case.report_identifiers.push(report.identifier);
+            case.first_reported_on = case.first_reported_on.min(report.created_on);
+            case.last_reported_on = case.last_reported_on.max(report.created_on);
+        }
+        Self::order(cases.into_values().collect())
+    }
+
+    // Merge the cases of multiple canisters, cases about the same subject are combined into one
+    pub fn merge(cases: Vec<ReportCase>) -> Vec<ReportCase> {
+        let mut merged: BTreeMap<Principal, ReportCase> = BTreeMap::new();
+        for case in cases {
+            match merged.get_mut(&case.subject) {
+                None => {
+                    merged.insert(case.subject, case);
+                }
+                Some(existing) => {
+                    existing.report_count += case.report_count;
+                    for reporter in case.reporters {
+                        if !existing.reporters.contains(&reporter) {
+                            existing.reporters.push(reporter);
+                        }
+                    }
+                    existing.report_identifiers.extend(case.report_identifiers);
+                    existing.first_reported_on =
+                        existing.first_reported_on.min(case.first_reported_on);
+                    existing.last_reported_on =
+                        existing.last_reported_on.max(case.last_reported_on);
+                }
+            }
+        }
+        Self::order(merged.into_values().collect())
+    }
+
+    fn order(mut cases: Vec<ReportCase>) -> Vec<ReportCase> {
+        cases.sort_by(|a, b| {
+            b.report_count
+                .cmp(&a.report_count)
+                .then(b.last_reported_on.cmp(&a.last_reported_on))
+        });
+        cases
+    }
+}
+
+// Aggregated counts of the reports of a group, `per_day` is keyed by the start of the day in nanoseconds
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ReportStats {
+    pub total: u64,
+    pub by_status: Vec<(ReportStatus, u64)>,
+    pub by_kind: Vec<(String, u64)>,
+    pub by_reason: Vec<(ReportReason, u64)>,
+    pub per_day: Vec<(u64, u64)>,
+}
+
+pub const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+
+impl ReportStats {
+    // Count a single report
+    pub fn add(&mut self, report: &ReportResponse) {
+        self.total += 1;
+        Self::add_count(&mut self.by_status, report.status.clone(), 1);
+        Self::add_count(&mut self.by_kind, report.subject_kind.clone(), 1);
+        Self::add_count(&mut self.by_reason, report.reason.clone(), 1);
+        Self::add_count(
+            &mut self.per_day,
+            report.created_on - report.created_on % NANOS_PER_DAY,
+            1,
+        );
+    }
+
+    // Sum the counts of other stats into these stats, used to combine the stats of multiple canisters
+    pub fn merge(&mut self, other: ReportStats) {
+        self.total += other.total;
+        for (status, count) in other.by_status {
+            Self::add_count(&mut self.by_status, status, count);
+        }
+        for (kind, count) in other.by_kind {
+            Self::add_count(&mut self.by_kind, kind, count);
+        }
+        for (reason, count) in other.by_reason {
+            Self::add_count(&mut self.by_reason, reason, count);
+        }
+        for (day, count) in other.per_day {
+            Self::add_count(&mut self.per_day, day, count);
+        }
+    }
+
+    fn add_count<K: Ord>(counts: &mut Vec<(K, u64)>, key: K, count: u64) {
+        match counts.binary_search_by(|(k, _)| k.cmp(&key)) {
+            Ok(index) => counts[index].1 += count,
+            Err(index) => counts.insert(index, (key, count)),
+        }
+    }
+}
+
+// Configuration of the child canisters, managed by the parent canister
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ReportConfig {
+    // Window in nanoseconds in which the same principal can not report the same subject in a group again, 0 disables the check
+    pub duplicate_window: u64,
+    // Maximum amount of reports a single principal can submit within the window, None disables the limit
+    pub caller_rate_limit: Option<RateLimit>,
+    // Maximum amount of reports that can be submitted for a single group within the window, None disables the limit
+    pub group_rate_limit: Option<RateLimit>,
+    // Maximum size in bytes of a chunk that a child canister sends to the parent canister, None uses the default
+    pub max_bytes_per_chunk: Option<usize>,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            // 24 hours
+            duplicate_window: 86_400_000_000_000,
+            // 10 reports per hour
+            caller_rate_limit: Some(RateLimit {
+                max_reports: 10,
+                window: 3_600_000_000_000,
+            }),
+            // 1000 reports per hour
+            group_rate_limit: Some(RateLimit {
+                max_reports: 1000,
+                window: 3_600_000_000_000,
+            }),
+            max_bytes_per_chunk: None,
+        }
+    }
+}
+
+// Sliding window rate limit, `window` is in nanoseconds
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct RateLimit {
+    pub max_reports: u64,
+    pub window: u64,
+}
+
+impl Storable for ReportConfig {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -66,6 +433,120 @@ pub enum ReportSort {
     Id(SortDirection),
     Kind(SortDirection),
     CreatedOn(SortDirection),
+    Reason(SortDirection),
+    Status(SortDirection),
+}
+
+// The value of the sort field of a report
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReportSortKey {
+    Id(u64),
+    Kind(String),
+    CreatedOn(u64),
+    Reason(ReportReason),
+    Status(ReportStatus),
+}
+
+// Position of the last report of a page, the next page starts after this report
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct ReportCursor {
+    pub keys: Vec<ReportSortKey>,
+    pub identifier: Principal,
+}
+
+// Page of reports that is continued with the `next_cursor`, `None` if there are no more reports
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CursorPagedResponse<T> {
+    pub data: Vec<T>,
+    pub limit: usize,
+    pub total: usize,
+    pub next_cursor: Option<String>,
+}
+
+// Fill level of a child canister, the parent canister uses this to install a spare child canister in time
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ChildCapacity {
+    pub entries_count: u64,
+    pub max_entries: u64,
+}
+
+impl ChildCapacity {
+    // Percentage of the max entries that is used
+    pub fn fill_percentage(&self) -> u64 {
+        match self.max_entries {
+            0 => 100,
+            max_entries => (self.entries_count.saturating_mul(100) / max_entries).min(100),
+        }
+    }
+}
+
+// Strategy the parent canister uses to place the reports of a group on the child canisters
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PlacementStrategy {
+    // Reports are stored on the available child canister in order of filling
+    #[default]
+    Fill,
+    // The child canisters that store the reports of a group are recorded by the parent canister,
+    // so the queries of a single group only call these child canisters
+    GroupAffinity,
+}
+
+// Child canister that could not be queried by the parent canister
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct FailedChild {
+    pub canister: Principal,
+    pub error: String,
+}
+
+// Response of a query over all the child canisters, `is_partial` is set when the data of one or more
+// child canisters is missing because they could not be queried, these are listed in `failed_children`
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct AggregatedResponse<T> {
+    pub data: T,
+    pub is_partial: bool,
+    pub failed_children: Vec<FailedChild>,
+}
+
+impl<T> AggregatedResponse<T> {
+    pub fn new(data: T, failed_children: Vec<FailedChild>) -> Self {
+        Self {
+            data,
+            is_partial: !failed_children.is_empty(),
+            failed_children,
+        }
+    }
+
+    // Transform the data and keep the failed children
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> AggregatedResponse<U> {
+        AggregatedResponse {
+            data: f(self.data),
+            is_partial: self.is_partial,
+            failed_children: self.failed_children,
+        }
+    }
+}
+
+// Chunk of serialized data that is send from a child to the parent canister, the chunks are cut at item boundaries
+// so every chunk can be deserialized on its own, all the chunks of a snapshot are cut from the same data
+// and `hash` is the sha256 hash of the bytes of all the chunks in order so the received chunks can be verified
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct DataChunk {
+    pub snapshot_id: u64,
+    pub total_bytes: usize,
+    pub chunk_count: usize,
+    pub hash: Vec<u8>,
+    pub chunk: usize,
+    pub bytes: Vec<u8>,
+}
+
+// Query the parent canister sends to the child canisters, each child only returns its first `limit` reports
+// in the order of the sorts (starting after the cursor), the parent merges these into a single page
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ReportQuery {
+    pub expression: ReportFilterExpression,
+    pub sorts: Vec<ReportSort>,
+    pub cursor: Option<ReportCursor>,
+    pub limit: usize,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -73,4 +554,249 @@ pub enum ReportFilter {
     Kind(String),
     CreatedOn(DateRange),
     ReportedBy(Principal),
+    Status(ReportStatus),
+    Reason(ReportReason),
+    // Case-insensitive substring of the report message
+    MessageContains(String),
+    Subject(Principal),
+    Group(Principal),
+}
+
+// Nested filter, for example `And([Filter(Kind("mbr")), Or([Filter(Status(Open)), Filter(ReportedBy(x))])])`
+// an empty `And` matches every report, an empty `Or` matches none
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum ReportFilterExpression {
+    Filter(ReportFilter),
+    And(Vec<ReportFilterExpression>),
+    Or(Vec<ReportFilterExpression>),
+    Not(Box<ReportFilterExpression>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helper::report;
+    use ic_scalable_misc::helpers::role_helper::default_roles;
+
+    const ACTIONS: [ReportAction; 6] = [
+        ReportAction::Submit,
+        ReportAction::ViewOwn,
+        ReportAction::ViewAll,
+        ReportAction::Annotate,
+        ReportAction::Resolve,
+        ReportAction::Delete,
+    ];
+
+    fn is_allowed(policy: &ReportPolicy, action: &ReportAction, role: &str) -> bool {
+        policy.is_allowed(action, &vec![role.to_string()], &default_roles())
+    }
+
+    #[test]
+    fn default_policy_maps_actions_to_permissions() {
+        let policy = ReportPolicy::default();
+        let expected = [
+            PermissionActionType::Read,
+            PermissionActionType::Read,
+            PermissionActionType::Edit,
+            PermissionActionType::Edit,
+            PermissionActionType::Edit,
+            PermissionActionType::Delete,
+        ];
+
+        for (action, permission) in ACTIONS.iter().zip(expected.iter()) {
+            assert_eq!(
+                format!("{:?}", policy.permission(action)),
+                format!("{:?}", permission),
+                "{:?}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn default_policy_for_default_roles() {
+        let policy = ReportPolicy::default();
+
+        for action in ACTIONS.iter() {
+            assert!(is_allowed(&policy, action, "owner"), "owner {:?}", action);
+            assert!(is_allowed(&policy, action, "admin"), "admin {:?}", action);
+        }
+
+        for role in ["moderator", "member"] {
+            assert!(is_allowed(&policy, &ReportAction::Submit, role));
+            assert!(is_allowed(&policy, &ReportAction::ViewOwn, role));
+            assert!(!is_allowed(&policy, &ReportAction::ViewAll, role));
+            assert!(!is_allowed(&policy, &ReportAction::Annotate, role));
+            assert!(!is_allowed(&policy, &ReportAction::Resolve, role));
+            assert!(!is_allowed(&policy, &ReportAction::Delete, role));
+        }
+    }
+
+    #[test]
+    fn default_policy_view_all_is_stricter_than_submit() {
+        let policy = ReportPolicy::default();
+
+        for role in ["owner", "admin", "moderator", "member", "visitor"] {
+            // Everyone who can see all the reports can also submit and see their own reports
+            if is_allowed(&policy, &ReportAction::ViewAll, role) {
+                assert!(is_allowed(&policy, &ReportAction::Submit, role), "{}", role);
+                assert!(
+                    is_allowed(&policy, &ReportAction::ViewOwn, role),
+                    "{}",
+                    role
+                );
+            }
+            // Viewing all the reports requires the same access as moderating them
+            assert_eq!(
+                is_allowed(&policy, &ReportAction::ViewAll, role),
+                is_allowed(&policy, &ReportAction::Annotate, role),
+                "{}",
+                role
+            );
+        }
+    }
+
+    #[test]
+    fn every_action_follows_the_configured_permission() {
+        let permissions = [
+            PermissionActionType::Read,
+            PermissionActionType::Write,
+            PermissionActionType::Edit,
+            PermissionActionType::Delete,
+        ];
+
+        for action in ACTIONS.iter() {
+            for permission in permissions.iter() {
+                let mut policy = ReportPolicy::default();
+                match action {
+                    ReportAction::Submit => policy.submit = permission.clone(),
+                    ReportAction::ViewOwn => policy.view_own = permission.clone(),
+                    ReportAction::ViewAll => policy.view_all = permission.clone(),
+                    ReportAction::Annotate => policy.annotate = permission.clone(),
+                    ReportAction::Resolve => policy.resolve = permission.clone(),
+                    ReportAction::Delete => policy.delete = permission.clone(),
+                }
+
+                // Only read access on the group for members and moderators in the default roles
+                let read_only = matches!(permission, PermissionActionType::Read);
+                assert!(is_allowed(&policy, action, "owner"));
+                assert!(is_allowed(&policy, action, "admin"));
+                assert_eq!(is_allowed(&policy, action, "moderator"), read_only);
+                assert_eq!(is_allowed(&policy, action, "member"), read_only);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_never_allowed() {
+        let policy = ReportPolicy::default();
+        for action in ACTIONS.iter() {
+            assert!(!is_allowed(&policy, action, "visitor"));
+            assert!(!policy.is_allowed(action, &vec![], &default_roles()));
+        }
+    }
+
+    #[test]
+    fn status_transitions() {
+        use ReportStatus::*;
+        // (from, to, allowed), every pair of statuses is listed
+        let transitions = [
+            (Open, Open, false),
+            (Open, InReview, true),
+            (Open, Resolved, false),
+            (Open, Dismissed, false),
+            (InReview, Open, false),
+            (InReview, InReview, false),
+            (InReview, Resolved, true),
+            (InReview, Dismissed, true),
+            // A closed report can only be reopened
+            (Resolved, Open, true),
+            (Resolved, InReview, false),
+            (Resolved, Resolved, false),
+            (Resolved, Dismissed, false),
+            (Dismissed, Open, true),
+            (Dismissed, InReview, false),
+            (Dismissed, Resolved, false),
+            (Dismissed, Dismissed, false),
+        ];
+
+        for (from, to, allowed) in transitions {
+            assert_eq!(
+                from.can_transition_to(&to),
+                allowed,
+                "{:?} -> {:?}",
+                from,
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn cases_merge_the_reports_per_subject() {
+        let subject = Principal::from_slice(&[1; 10]);
+        let other_subject = Principal::from_slice(&[2; 10]);
+        let reporter = Principal::from_slice(&[3; 10]);
+
+        let mut reports = vec![report(1, 10), report(2, 30), report(3, 20)];
+        reports[0].subject = subject;
+        reports[1].subject = subject;
+        reports[1].reported_by = reporter;
+        reports[2].subject = other_subject;
+
+        let cases = ReportCase::from_reports(reports);
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].subject, subject);
+        assert_eq!(cases[0].report_count, 2);
+        assert_eq!(cases[0].reporters, vec![Principal::anonymous(), reporter]);
+        assert_eq!(cases[0].first_reported_on, 10);
+        assert_eq!(cases[0].last_reported_on, 30);
+        assert_eq!(cases[1].subject, other_subject);
+        assert_eq!(cases[1].report_count, 1);
+    }
+
+    #[test]
+    fn cases_of_multiple_canisters_are_merged_per_subject() {
+        let subject = Principal::from_slice(&[1; 10]);
+        let other_subject = Principal::from_slice(&[2; 10]);
+        let reporter = Principal::from_slice(&[3; 10]);
+
+        let mut first = vec![report(1, 10), report(2, 40)];
+        first[0].subject = subject;
+        first[1].subject = other_subject;
+        let mut second = vec![report(3, 30), report(4, 20)];
+        second[0].subject = subject;
+        second[0].reported_by = reporter;
+        second[1].subject = subject;
+
+        let cases = ReportCase::merge(
+            [
+                ReportCase::from_reports(first),
+                ReportCase::from_reports(second),
+            ]
+            .concat(),
+        );
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].subject, subject);
+        assert_eq!(cases[0].report_count, 3);
+        assert_eq!(cases[0].reporters, vec![Principal::anonymous(), reporter]);
+        assert_eq!(cases[0].report_identifiers.len(), 3);
+        assert_eq!(cases[0].first_reported_on, 10);
+        assert_eq!(cases[0].last_reported_on, 30);
+        assert_eq!(cases[1].subject, other_subject);
+    }
+
+    #[test]
+    fn merged_stats_sum_the_counts() {
+        let mut first = ReportStats::default();
+        first.add(&report(1, NANOS_PER_DAY + 1));
+        first.add(&report(2, 5));
+
+        let mut second = ReportStats::default();
+        second.add(&report(3, NANOS_PER_DAY + 2));
+
+        first.merge(second);
+        assert_eq!(first.total, 3);
+        assert_eq!(first.by_status, vec![(ReportStatus::Open, 3)]);
+        assert_eq!(first.per_day, vec![(0, 1), (NANOS_PER_DAY, 2)]);
+    }
 }
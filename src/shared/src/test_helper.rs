@@ -0,0 +1,22 @@
+use candid::Principal;
+use ic_scalable_misc::models::identifier_model::Identifier;
+
+use crate::report_model::{ReportReason, ReportResponse, ReportStatus};
+
+// Report used by the tests, only the identifier and created on date differ
+pub fn report(id: u64, created_on: u64) -> ReportResponse {
+    ReportResponse {
+        identifier: Identifier::new(id, Principal::anonymous(), "rpt".to_string())
+            .unwrap()
+            .encode()
+            .unwrap(),
+        reported_by: Principal::anonymous(),
+        group_identifier: Principal::anonymous(),
+        subject: Principal::anonymous(),
+        subject_kind: "mbr".to_string(),
+        message: String::default(),
+        created_on,
+        status: ReportStatus::Open,
+        reason: ReportReason::Other,
+    }
+}
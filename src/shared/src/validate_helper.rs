@@ -0,0 +1,17 @@
+use ic_scalable_misc::{
+    enums::{api_error_type::ApiError, validation_type::ValidationType},
+    helpers::validation_helper::Validator,
+    models::validation_models::ValidateField,
+};
+
+use crate::report_model::PostReport;
+
+// Validate the report before it is stored, used by the child canister and by the parent canister before forwarding
+pub fn validate_post_report(post_report: PostReport) -> Result<(), ApiError> {
+    let validator_fields = vec![ValidateField(
+        ValidationType::StringLength(post_report.message, 0, 500),
+        "message".to_string(),
+    )];
+
+    Validator(validator_fields).validate()
+}